@@ -83,6 +83,7 @@
                 with pkgs;
                 [
                   dbus
+                  openssl
                   pipewire
                 ]
               );
@@ -0,0 +1,165 @@
+use crate::{
+    cli::{FrameDiffOptions, ImageProcessingOptions, PlaybackOptions},
+    error::{Error, Result},
+    frame_pacing::FramePacer,
+    image_processing::{ImageProcessingPipeline, ProcessedFrame},
+    transport::Transport,
+    wall::WallSender,
+};
+use image::{
+    AnimationDecoder, DynamicImage, Frame, ImageFormat, ImageReader,
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+};
+use log::{debug, info, warn};
+use std::time::Duration;
+
+/// Browsers show frames with a very short delay for a longer time, and many files rely on that.
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// Loads all frames of an animated GIF, APNG or WebP file.
+///
+/// Returns `None` for other formats and images with only one frame.
+pub fn load_animation(file_name: &str) -> Result<Option<Vec<Frame>>> {
+    let reader = ImageReader::open(file_name)
+        .map_err(|e| Error::io("failed to open image file", e))?
+        .with_guessed_format()
+        .map_err(|e| Error::io("failed to read image file", e))?;
+    let Some(format) = reader.format() else {
+        return Ok(None);
+    };
+    let reader = reader.into_inner();
+
+    let frames = match format {
+        ImageFormat::Gif => GifDecoder::new(reader)
+            .map_err(|e| Error::image("failed to read gif", e))?
+            .into_frames(),
+        ImageFormat::Png => {
+            let decoder =
+                PngDecoder::new(reader).map_err(|e| Error::image("failed to read png", e))?;
+            if !decoder
+                .is_apng()
+                .map_err(|e| Error::image("failed to read png", e))?
+            {
+                return Ok(None);
+            }
+            decoder
+                .apng()
+                .map_err(|e| Error::image("failed to read apng", e))?
+                .into_frames()
+        }
+        ImageFormat::WebP => {
+            let decoder =
+                WebPDecoder::new(reader).map_err(|e| Error::image("failed to read webp", e))?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+        _ => return Ok(None),
+    };
+
+    let frames = frames
+        .collect_frames()
+        .map_err(|e| Error::image("failed to decode animation frames", e))?;
+    debug!("loaded {} frames of {format:?} animation", frames.len());
+    if frames.len() <= 1 {
+        return Ok(None);
+    }
+    Ok(Some(frames))
+}
+
+pub fn play_animation(
+    connection: &Transport,
+    frames: Vec<Frame>,
+    playback_options: PlaybackOptions,
+    processing_options: ImageProcessingOptions,
+    frame_diff_options: FrameDiffOptions,
+) -> Result<()> {
+    // temporal dithering depends on the frame shown before, so the frames differ between loops
+    let reuse_frames = !processing_options.temporal;
+    let mut pipeline = ImageProcessingPipeline::new(processing_options, connection.wall_layout());
+    let mut sender = WallSender::new(connection, frame_diff_options);
+    let mut pacer = FramePacer::new(&playback_options, connection.is_live());
+    let start = playback_options.start.unwrap_or_default();
+
+    let frames: Vec<_> = frames
+        .into_iter()
+        .map(|frame| (frame_delay(&frame), DynamicImage::from(frame.into_buffer())))
+        .collect();
+    // frames are processed when first shown and reused when looping, if possible
+    let mut processed: Vec<Option<ProcessedFrame>> = vec![None; frames.len()];
+
+    info!("playing animation with {} frames", frames.len());
+    loop {
+        let mut position = Duration::ZERO;
+        let mut range_end = Duration::ZERO;
+        let mut frames_in_range = 0;
+
+        for (index, (delay, image)) in frames.iter().enumerate() {
+            let frame_position = position;
+            position += *delay;
+
+            if position <= start {
+                continue;
+            }
+            if playback_options
+                .end
+                .is_some_and(|end| frame_position >= end)
+                || playback_options
+                    .frames
+                    .is_some_and(|max| frames_in_range >= max)
+            {
+                break;
+            }
+            frames_in_range += 1;
+            range_end = position;
+
+            let Some(due) = pacer.schedule(frame_position.saturating_sub(start)) else {
+                sender.skip();
+                continue;
+            };
+
+            let frame = if reuse_frames {
+                processed[index]
+                    .get_or_insert_with(|| pipeline.process(image.clone()))
+                    .clone()
+            } else {
+                pipeline.process(image.clone())
+            };
+
+            FramePacer::wait_until(due);
+            sender.send(frame)?;
+        }
+
+        if frames_in_range == 0 {
+            warn!("no frames in the selected range, stopping playback");
+            break;
+        }
+
+        // show the last frame for its full delay
+        let range_end = playback_options
+            .end
+            .map_or(range_end, |end| end.min(range_end));
+        pacer.finish(range_end.saturating_sub(start));
+
+        if !pacer.next_iteration() {
+            break;
+        }
+    }
+
+    info!(
+        "finished animation, dropped {} frames",
+        pacer.dropped_frames()
+    );
+    Ok(())
+}
+
+fn frame_delay(frame: &Frame) -> Duration {
+    let delay = Duration::from(frame.delay());
+    if delay < MIN_FRAME_DELAY {
+        DEFAULT_FRAME_DELAY
+    } else {
+        delay
+    }
+}
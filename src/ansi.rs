@@ -0,0 +1,239 @@
+/// Parameters beyond this are dropped, real sequences use far fewer.
+const MAX_PARAMS: usize = 16;
+
+/// Something a terminal has to do, as parsed from its input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// A character to draw at the cursor.
+    Print(char),
+    /// A control character like line feed or backspace.
+    Control(char),
+    /// A control sequence, e.g. `ESC [ 1 ; 31 m`. Omitted parameters are 0.
+    Csi {
+        /// Marks private sequences, e.g. the `?` of `ESC [ ? 25 h`.
+        private: Option<char>,
+        params: Vec<u16>,
+        intermediate: Option<char>,
+        final_char: char,
+    },
+    /// An escape sequence, e.g. `ESC 7` or `ESC ( B`.
+    Escape {
+        intermediate: Option<char>,
+        final_char: char,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+    /// Operating system commands and other strings, like window titles, are skipped.
+    String,
+}
+
+/// Splits terminal output into characters and escape sequences, following ECMA-48 as far as
+/// terminals like xterm implement it.
+///
+/// Malformed sequences are skipped, so they do not show up as garbage.
+#[derive(Debug)]
+pub struct Parser {
+    state: State,
+    private: Option<char>,
+    params: Vec<u16>,
+    param: Option<u16>,
+    intermediate: Option<char>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self {
+            state: State::Ground,
+            private: None,
+            params: Vec::new(),
+            param: None,
+            intermediate: None,
+        }
+    }
+}
+
+impl Parser {
+    /// Returns an action once a character or a complete sequence was read.
+    pub fn advance(&mut self, character: char) -> Option<Action> {
+        match (self.state, character) {
+            // cancel any sequence
+            (_, '\x18' | '\x1a') => {
+                self.state = State::Ground;
+                None
+            }
+            (State::String, '\x07') => {
+                self.state = State::Ground;
+                None
+            }
+            (_, '\x1b') => {
+                self.start(State::Escape);
+                None
+            }
+            (_, '\u{9b}') => {
+                self.start(State::Csi);
+                None
+            }
+            (State::String, _) => None,
+            // control characters are executed even in the middle of sequences
+            (_, '\x7f') => None,
+            (_, character) if character.is_ascii_control() => Some(Action::Control(character)),
+            (State::Ground, character) if character.is_control() => None,
+            (State::Ground, character) => Some(Action::Print(character)),
+            (State::Escape, '[') if self.intermediate.is_none() => {
+                self.start(State::Csi);
+                None
+            }
+            (State::Escape, ']' | 'P' | 'X' | '^' | '_') if self.intermediate.is_none() => {
+                self.state = State::String;
+                None
+            }
+            (State::Escape | State::Csi, ' '..='/') => {
+                self.intermediate = Some(character);
+                None
+            }
+            (State::Escape, '0'..='~') => {
+                self.state = State::Ground;
+                Some(Action::Escape {
+                    intermediate: self.intermediate,
+                    final_char: character,
+                })
+            }
+            (State::Csi, '0'..='9') if self.intermediate.is_none() => {
+                let digit = character as u16 - '0' as u16;
+                let param = self.param.unwrap_or(0);
+                self.param = Some(param.saturating_mul(10).saturating_add(digit));
+                None
+            }
+            (State::Csi, ';' | ':') if self.intermediate.is_none() => {
+                self.push_param();
+                None
+            }
+            (State::Csi, '<'..='?') if self.params.is_empty() && self.param.is_none() => {
+                self.private = Some(character);
+                None
+            }
+            (State::Csi, '@'..='~') => {
+                if self.param.is_some() || !self.params.is_empty() {
+                    self.push_param();
+                }
+                self.state = State::Ground;
+                Some(Action::Csi {
+                    private: self.private,
+                    params: std::mem::take(&mut self.params),
+                    intermediate: self.intermediate,
+                    final_char: character,
+                })
+            }
+            // anything else is malformed
+            _ => {
+                self.state = State::Ground;
+                None
+            }
+        }
+    }
+
+    fn start(&mut self, state: State) {
+        self.state = state;
+        self.private = None;
+        self.params.clear();
+        self.param = None;
+        self.intermediate = None;
+    }
+
+    fn push_param(&mut self) {
+        let param = self.param.take().unwrap_or(0);
+        if self.params.len() < MAX_PARAMS {
+            self.params.push(param);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Vec<Action> {
+        let mut parser = Parser::default();
+        text.chars()
+            .filter_map(|character| parser.advance(character))
+            .collect()
+    }
+
+    fn csi(private: Option<char>, params: &[u16], final_char: char) -> Action {
+        Action::Csi {
+            private,
+            params: params.to_vec(),
+            intermediate: None,
+            final_char,
+        }
+    }
+
+    #[test]
+    fn characters_and_controls() {
+        assert_eq!(
+            parse("a\r\nä"),
+            [
+                Action::Print('a'),
+                Action::Control('\r'),
+                Action::Control('\n'),
+                Action::Print('ä'),
+            ]
+        );
+    }
+
+    #[test]
+    fn control_sequence_parameters() {
+        assert_eq!(parse("\x1b[1;31m"), [csi(None, &[1, 31], 'm')]);
+        assert_eq!(parse("\x1b[m"), [csi(None, &[], 'm')]);
+        // omitted parameters are 0
+        assert_eq!(parse("\x1b[;5H"), [csi(None, &[0, 5], 'H')]);
+        assert_eq!(parse("\x1b[?25;1049h"), [csi(Some('?'), &[25, 1049], 'h')]);
+        assert_eq!(parse("\u{9b}2J"), [csi(None, &[2], 'J')]);
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(
+            parse("\x1b7\x1b(B"),
+            [
+                Action::Escape {
+                    intermediate: None,
+                    final_char: '7',
+                },
+                Action::Escape {
+                    intermediate: Some('('),
+                    final_char: 'B',
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn strings_and_cancelled_sequences_are_skipped() {
+        assert_eq!(parse("\x1b]0;title\x07x"), [Action::Print('x')]);
+        assert_eq!(
+            parse("\x1b]0;title\x1b\\x"),
+            [
+                Action::Escape {
+                    intermediate: None,
+                    final_char: '\\',
+                },
+                Action::Print('x'),
+            ]
+        );
+        assert_eq!(parse("\x1b[12\x18x"), [Action::Print('x')]);
+    }
+
+    #[test]
+    fn controls_inside_sequences_are_executed() {
+        assert_eq!(
+            parse("\x1b[2\nA"),
+            [Action::Control('\n'), csi(None, &[2], 'A')]
+        );
+    }
+}
@@ -1,8 +1,11 @@
-use crate::{cli::BrightnessCommand, transport::Transport};
+use crate::{cli::BrightnessCommand, error::Result, transport::Transport};
 use log::info;
 use servicepoint::{Brightness, GlobalBrightnessCommand};
 
-pub(crate) fn brightness(connection: &Transport, brightness_command: BrightnessCommand) {
+pub(crate) fn brightness(
+    connection: &Transport,
+    brightness_command: BrightnessCommand,
+) -> Result<()> {
     match brightness_command {
         BrightnessCommand::Max => brightness_set(connection, Brightness::MAX),
         BrightnessCommand::Min => brightness_set(connection, Brightness::MIN),
@@ -12,9 +15,8 @@ pub(crate) fn brightness(connection: &Transport, brightness_command: BrightnessC
     }
 }
 
-pub(crate) fn brightness_set(connection: &Transport, brightness: Brightness) {
-    connection
-        .send_command(GlobalBrightnessCommand::from(brightness))
-        .expect("Failed to set brightness");
+pub(crate) fn brightness_set(connection: &Transport, brightness: Brightness) -> Result<()> {
+    connection.send_command(GlobalBrightnessCommand::from(brightness))?;
     info!("set brightness to {brightness:?}");
+    Ok(())
 }
@@ -0,0 +1,99 @@
+use crate::cli::Charset;
+use servicepoint::{
+    CharGrid, CharGridCommand, Cp437GridCommand, Origin, Tiles, TypedCommand, cp437::char_to_cp437,
+};
+
+/// Characters the ROM of the display does not contain, mapped to the closest character it does.
+const TRANSLITERATIONS: &[(&str, char)] = &[
+    ("ÀÁÂÃĀĂĄ", 'A'),
+    ("ãāăą", 'a'),
+    ("ĆĈĊČ", 'C'),
+    ("ćĉċč", 'c'),
+    ("ĎĐ", 'D'),
+    ("ďđ", 'd'),
+    ("ÈÊËĒĔĖĘĚ", 'E'),
+    ("ēĕėęě", 'e'),
+    ("ĜĞĠĢ", 'G'),
+    ("ĝğġģ", 'g'),
+    ("ÌÍÎÏĨĪĬĮİ", 'I'),
+    ("ĩīĭįı", 'i'),
+    ("ŁĹĻĽ", 'L'),
+    ("łĺļľ", 'l'),
+    ("ŃŅŇ", 'N'),
+    ("ńņň", 'n'),
+    ("ÒÓÔÕØŌŎŐ", 'O'),
+    ("õøōŏő", 'o'),
+    ("ŔŖŘ", 'R'),
+    ("ŕŗř", 'r'),
+    ("ŚŜŞŠ", 'S'),
+    ("śŝşš", 's'),
+    ("ŢŤ", 'T'),
+    ("ţť", 't'),
+    ("ÙÚÛŨŪŬŮŰŲ", 'U'),
+    ("ũūŭůűų", 'u'),
+    ("ÝŸ", 'Y'),
+    ("ý", 'y'),
+    ("ŹŻŽ", 'Z'),
+    ("źżž", 'z'),
+    ("ẞ", 'ß'),
+    ("‘’‚‛′", '\''),
+    ("“”„‟″", '"'),
+    ("‐‑‒–—―−", '-'),
+    ("‹", '<'),
+    ("›", '>'),
+    ("…", '.'),
+    ("×", 'x'),
+    ("€", 'E'),
+    ("©", 'c'),
+    ("®", 'R'),
+    ("✓✔", '√'),
+    ("●◦⁃", '•'),
+    ("▪◼", '■'),
+    ("━╸╺╼╾", '─'),
+    ("┃╹╻╽╿", '│'),
+    ("┏╭┍┎", '┌'),
+    ("┓╮┑┒", '┐'),
+    ("┗╰┕┖", '└'),
+    ("┛╯┙┚", '┘'),
+    ("┣┝┞┟┠┡┢", '├'),
+    ("┫┥┦┧┨┩┪", '┤'),
+    ("┳┯┰┱┲┭┮", '┬'),
+    ("┻┷┸┹┺┵┶", '┴'),
+    ("╋┿╀╁╂╃╄╅╆╇╈╉╊┽┾", '┼'),
+    ("╌╍┄┅┈┉", '─'),
+    ("╎╏┆┇┊┋", '│'),
+    ("\t\u{a0}\u{2002}\u{2003}\u{2009}", ' '),
+];
+
+/// Encodes text for the display. With [Charset::Cp437], characters are transliterated to what
+/// the character ROM of the display can show.
+pub fn text_command(grid: &CharGrid, origin: Origin<Tiles>, charset: Charset) -> TypedCommand {
+    match charset {
+        Charset::Cp437 => Cp437GridCommand {
+            origin,
+            grid: grid.map(to_cp437),
+        }
+        .into(),
+        Charset::Utf8 => CharGridCommand {
+            origin,
+            grid: grid.clone(),
+        }
+        .into(),
+    }
+}
+
+/// Characters without an equivalent in CP437 are replaced with `?`.
+pub fn to_cp437(character: char) -> u8 {
+    // the ROM also has a space at 0xFF, which the lookup of servicepoint would return
+    if character.is_ascii() && !character.is_ascii_control() {
+        return character as u8;
+    }
+    let code = char_to_cp437(character);
+    if code != b'?' {
+        return code;
+    }
+    TRANSLITERATIONS
+        .iter()
+        .find(|(from, _)| from.contains(character))
+        .map_or(code, |(_, to)| char_to_cp437(*to))
+}
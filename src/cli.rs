@@ -1,3 +1,7 @@
+use crate::{text_layout::Region, wall::WallLayout};
+use servicepoint::{TILE_HEIGHT, TILE_WIDTH};
+use std::time::Duration;
+
 #[derive(clap::Parser, std::fmt::Debug)]
 #[clap(
     version,
@@ -5,13 +9,27 @@
     about = "A command line interface for the ServicePoint display."
 )]
 pub struct Cli {
+    #[command(flatten)]
+    pub transport_options: TransportOptions,
+    #[clap(subcommand)]
+    pub command: Mode,
+    #[clap(short, long, help = "verbose logging")]
+    pub verbose: bool,
+}
+
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct TransportOptions {
     #[arg(
         short,
         long,
-        help = "ip:port of the servicepoint display",
-        default_value = "127.0.0.1:2342"
+        help = "ip:port of the servicepoint display, or the output directory for the file transport, which has no default. \
+        Can be repeated to send to multiple displays. \
+        Destinations starting with udp://, ws://, wss:// or file:// use the matching transport.",
+        default_value = "127.0.0.1:2342",
+        default_value_if("transport", "file", None),
+        required_if_eq("transport", "file")
     )]
-    pub destination: String,
+    pub destination: Vec<String>,
     #[arg(
         short,
         long,
@@ -20,14 +38,218 @@ pub struct Cli {
         default_value = "udp"
     )]
     pub transport: TransportType,
-    #[clap(subcommand)]
-    pub command: Mode,
-    #[clap(short, long, help = "verbose logging")]
-    pub verbose: bool,
+    #[arg(
+        long,
+        help = "format of the frames written by the file transport",
+        value_enum,
+        default_value = "png"
+    )]
+    pub file_format: FileFormat,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "record all sent packets into a file, which can be sent again with the replay command"
+    )]
+    pub record: Option<String>,
+    #[arg(
+        long,
+        value_name = "COLUMNSxROWS",
+        value_parser = parse_wall_layout,
+        conflicts_with = "record",
+        help = "show images and videos across multiple displays, e.g. 2x1 for two displays next to each other. \
+        Needs one destination per display, ordered row by row."
+    )]
+    pub wall: Option<WallLayout>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "lzma",
+        help = "compression of bitmaps sent to the display. \
+        Use none or zstd if sending is limited by the CPU, bzip2 or lzma if it is limited by the network. \
+        auto tries all of them on the first frames and picks the best trade-off of time and size."
+    )]
+    pub compression: CompressionMode,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        help = "print every packet to stdout before it is sent"
+    )]
+    pub dump: Option<DumpFormat>,
+    #[arg(
+        long,
+        help = "do not connect to the destinations and do not send anything, e.g. to only --dump the packets"
+    )]
+    pub dry_run: bool,
+    #[arg(
+        long,
+        help = "periodically log how many packets and bytes were sent and how many frames were skipped"
+    )]
+    pub stats: bool,
+    #[command(flatten)]
+    pub rate_limit_options: RateLimitOptions,
+    #[command(flatten)]
+    pub websocket_options: WebSocketOptions,
+    #[command(flatten)]
+    pub reconnect_options: ReconnectOptions,
+}
+
+/// Limits for sending to each display, so its receiver is not flooded.
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct RateLimitOptions {
+    #[arg(
+        long,
+        value_name = "PACKETS",
+        value_parser = parse_positive_f64,
+        help = "maximum packets per second sent to each display, defaults to 16 packets per 30 ms"
+    )]
+    pub max_packet_rate: Option<f64>,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        value_parser = parse_positive_f64,
+        help = "maximum bytes per second sent to each display, defaults to one uncompressed frame per 30 ms"
+    )]
+    pub max_byte_rate: Option<f64>,
+    #[arg(
+        long,
+        conflicts_with_all = ["max_packet_rate", "max_byte_rate"],
+        help = "send packets as fast as possible"
+    )]
+    pub no_rate_limit: bool,
+}
+
+/// Options for WebSocket destinations, e.g. a relay behind a reverse proxy.
+#[derive(clap::Parser, Clone)]
+pub struct WebSocketOptions {
+    #[arg(
+        long = "header",
+        value_name = "NAME:VALUE",
+        value_parser = parse_header,
+        help = "additional HTTP header for the WebSocket handshake, can be repeated"
+    )]
+    pub headers: Vec<(String, String)>,
+    #[arg(
+        long,
+        env = "SERVICEPOINT_TOKEN",
+        hide_env_values = true,
+        help = "bearer token for authenticating at WebSocket destinations"
+    )]
+    pub token: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "read the bearer token from a file instead, takes precedence over --token"
+    )]
+    pub token_file: Option<String>,
+    #[arg(
+        long,
+        value_name = "USER:PASSWORD",
+        conflicts_with_all = ["token", "token_file"],
+        help = "credentials for HTTP basic authentication at WebSocket destinations, used instead of a token"
+    )]
+    pub basic_auth: Option<String>,
+    #[arg(
+        long = "subprotocol",
+        value_name = "NAME",
+        help = "WebSocket subprotocol to request, can be repeated"
+    )]
+    pub subprotocols: Vec<String>,
+    #[arg(
+        long = "ca-cert",
+        value_name = "FILE",
+        help = "PEM file with additional root certificates for wss:// destinations, can be repeated"
+    )]
+    pub ca_certs: Vec<String>,
+    #[arg(
+        long,
+        help = "do not verify TLS certificates of wss:// destinations. Only use this for testing."
+    )]
+    pub insecure: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "record the packets received from WebSocket destinations, e.g. what other clients of a relay draw"
+    )]
+    pub record_received: Option<String>,
+}
+
+/// Written by hand, so credentials do not end up in debug logs. Header values are hidden as
+/// well, as they often contain credentials, too.
+impl std::fmt::Debug for WebSocketOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const REDACTED: &str = "<redacted>";
+        let headers: Vec<_> = self
+            .headers
+            .iter()
+            .map(|(name, _)| (name, REDACTED))
+            .collect();
+        f.debug_struct("WebSocketOptions")
+            .field("headers", &headers)
+            .field("token", &self.token.as_ref().map(|_| REDACTED))
+            .field("token_file", &self.token_file)
+            .field("basic_auth", &self.basic_auth.as_ref().map(|_| REDACTED))
+            .field("subprotocols", &self.subprotocols)
+            .field("ca_certs", &self.ca_certs)
+            .field("insecure", &self.insecure)
+            .field("record_received", &self.record_received)
+            .finish()
+    }
+}
+
+/// How lost WebSocket connections are reopened.
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct ReconnectOptions {
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 10,
+        help = "how often to try reconnecting a lost WebSocket connection before giving up, 0 to retry forever"
+    )]
+    pub reconnect_attempts: u32,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value = "0.5",
+        value_parser = parse_seconds,
+        help = "time to wait before the first reconnect attempt, doubled after every failed attempt"
+    )]
+    pub reconnect_delay: Duration,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value = "30",
+        value_parser = parse_seconds,
+        help = "maximum time to wait between reconnect attempts"
+    )]
+    pub reconnect_max_delay: Duration,
 }
 
 #[derive(clap::Parser, std::fmt::Debug)]
 pub enum Mode {
+    #[command(flatten)]
+    Display(DisplayMode),
+    #[command(
+        visible_alias = "serve",
+        about = "Emulate a display in the terminal, e.g. to test the other commands without the real display"
+    )]
+    Emulate {
+        #[command(flatten)]
+        emulator_options: EmulatorOptions,
+    },
+    #[command(
+        visible_alias = "decode",
+        about = "Describe raw packets, e.g. to debug what another client sends to the display"
+    )]
+    Inspect {
+        #[command(flatten)]
+        inspect_options: InspectOptions,
+    },
+}
+
+/// The modes that send to the displays.
+#[derive(clap::Parser, std::fmt::Debug)]
+pub enum DisplayMode {
     #[command(visible_alias = "r", about = "Reset both pixels and brightness")]
     Reset {
         #[arg(short, long, help = "hard reset screen")]
@@ -48,6 +270,13 @@ pub enum Mode {
         #[clap(subcommand)]
         text_command: TextCommand,
     },
+    #[command(about = "Send the packets of a recording again, with the original timing")]
+    Replay {
+        #[command(flatten)]
+        replay_options: ReplayOptions,
+        #[command(flatten)]
+        playback_options: PlaybackOptions,
+    },
 }
 
 #[derive(clap::Parser, std::fmt::Debug)]
@@ -66,13 +295,18 @@ pub enum PixelCommand {
     On,
     #[command(
         visible_alias = "i",
-        about = "Send an image file (e.g. jpeg or png) to the display."
+        about = "Send an image file (e.g. jpeg or png) to the display. \
+        Animated GIF, APNG and WebP files are played back."
     )]
     Image {
         #[command(flatten)]
         send_image_options: SendImageOptions,
         #[command(flatten)]
+        playback_options: PlaybackOptions,
+        #[command(flatten)]
         image_processing_options: ImageProcessingOptions,
+        #[command(flatten)]
+        frame_diff_options: FrameDiffOptions,
     },
     #[command(
         visible_alias = "v",
@@ -82,7 +316,11 @@ pub enum PixelCommand {
         #[command(flatten)]
         send_image_options: SendImageOptions,
         #[command(flatten)]
+        playback_options: PlaybackOptions,
+        #[command(flatten)]
         image_processing_options: ImageProcessingOptions,
+        #[command(flatten)]
+        frame_diff_options: FrameDiffOptions,
     },
     #[command(
         visible_alias = "s",
@@ -95,6 +333,8 @@ pub enum PixelCommand {
         stream_options: StreamScreenOptions,
         #[command(flatten)]
         image_processing: ImageProcessingOptions,
+        #[command(flatten)]
+        frame_diff_options: FrameDiffOptions,
     },
 }
 
@@ -116,11 +356,55 @@ pub enum BrightnessCommand {
     Min,
 }
 
-#[derive(clap::ValueEnum, Clone, Debug)]
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
 pub enum TransportType {
     Udp,
     WebSocket,
     Fake,
+    File,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum FileFormat {
+    Png,
+    Pbm,
+    Gif,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum DumpFormat {
+    Hex,
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum CompressionMode {
+    None,
+    #[value(alias = "lz")]
+    Zlib,
+    Bzip2,
+    Lzma,
+    Zstd,
+    Auto,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum DitherAlgorithm {
+    Ostromoukhov,
+    FloydSteinberg,
+    Atkinson,
+    JarvisJudiceNinke,
+    Sierra,
+    Bayer2x2,
+    Bayer4x4,
+    Bayer8x8,
+    BlueNoise,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum RenderStyle {
+    Braille,
+    HalfBlock,
 }
 
 #[derive(clap::Parser, std::fmt::Debug)]
@@ -130,16 +414,169 @@ pub enum TextCommand {
         about = "Pipe text to the display, example: `journalctl | servicepoint-cli text stdin`"
     )]
     Stdin {
+        #[command(flatten)]
+        stdin_options: StdinOptions,
+        #[command(flatten)]
+        text_options: TextOptions,
+    },
+    #[command(
+        about = "Run a program in a terminal as large as the display, example: `servicepoint-cli text exec -- htop`"
+    )]
+    Exec {
+        #[command(flatten)]
+        exec_options: ExecOptions,
+        #[command(flatten)]
+        text_options: TextOptions,
+    },
+    #[command(
+        about = "Show a message, example: `servicepoint-cli text show --align center 'Closed' 'back at 18:00'`"
+    )]
+    Show {
         #[arg(
-            long,
-            short,
-            default_value_t = false,
-            help = "Wait for a short amount of time before sending the next line"
+            required = true,
+            help = "The message. Every argument starts a new line, as do line breaks in them."
         )]
-        slow: bool,
+        lines: Vec<String>,
+        #[command(flatten)]
+        layout_options: LayoutOptions,
+        #[command(flatten)]
+        text_options: TextOptions,
+    },
+    #[command(about = "Show the contents of a text file")]
+    File {
+        #[arg(help = "The text file, or - to read from stdin")]
+        file_name: String,
+        #[command(flatten)]
+        layout_options: LayoutOptions,
+        #[command(flatten)]
+        text_options: TextOptions,
     },
 }
 
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct StdinOptions {
+    #[arg(
+        long,
+        short,
+        default_value_t = false,
+        help = "Wait for a short amount of time before sending the next line"
+    )]
+    pub slow: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "truncate",
+        help = "How lines longer than the display is wide are shown. char and word continue them \
+        on the next row."
+    )]
+    pub wrap: WrapMode,
+
+    #[arg(
+        long,
+        conflicts_with = "wrap",
+        help = "Scroll lines that are too long horizontally instead of cutting them off"
+    )]
+    pub marquee: bool,
+}
+
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct ExecOptions {
+    #[arg(
+        long,
+        default_value = "xterm",
+        help = "Terminal type the program is told about in TERM"
+    )]
+    pub term: String,
+
+    #[arg(
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        value_name = "COMMAND",
+        help = "The program to run and its arguments. Key presses are passed on to it."
+    )]
+    pub command: Vec<String>,
+}
+
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct LayoutOptions {
+    #[arg(
+        long,
+        value_enum,
+        default_value = "left",
+        help = "Horizontal alignment of every line"
+    )]
+    pub align: Align,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "top",
+        help = "Vertical alignment of the text as a whole"
+    )]
+    pub valign: VerticalAlign,
+
+    #[arg(
+        long,
+        value_name = "X,Y,WIDTHxHEIGHT",
+        value_parser = parse_region,
+        help = "Part of the display the text is shown in, in characters, e.g. 0,10,56x10 for the \
+        lower half. Text outside of it is left as it is. [default: the whole display]"
+    )]
+    pub region: Option<Region>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "word",
+        help = "How lines longer than the region is wide are shown"
+    )]
+    pub wrap: WrapMode,
+
+    #[arg(long, help = "Clear the whole display before showing the text")]
+    pub clear: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum VerticalAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum WrapMode {
+    Truncate,
+    Char,
+    Word,
+}
+
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct TextOptions {
+    #[arg(
+        long,
+        value_enum,
+        default_value = "cp437",
+        help = "Character set of the display. cp437 works with every firmware, characters it does \
+        not contain are replaced with similar ones or '?'. utf8 needs a firmware that supports it."
+    )]
+    pub charset: Charset,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Charset {
+    Cp437,
+    Utf8,
+}
+
 #[derive(clap::Parser, std::fmt::Debug, Clone)]
 pub struct StreamScreenOptions {
     #[arg(
@@ -151,6 +588,66 @@ pub struct StreamScreenOptions {
     pub pointer: bool,
 }
 
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct EmulatorOptions {
+    #[arg(
+        long,
+        help = "ip:port to receive UDP packets on",
+        default_value = "127.0.0.1:2342"
+    )]
+    pub bind: String,
+
+    #[arg(long, help = "ip:port to accept WebSocket connections on")]
+    pub websocket: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "braille",
+        help = "How pixels are drawn in the terminal. Half blocks are larger, but need a wide terminal."
+    )]
+    pub render: RenderStyle,
+}
+
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct ReplayOptions {
+    #[arg(help = "A file created with --record")]
+    pub file_name: String,
+}
+
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct InspectOptions {
+    #[arg(help = "File containing the packets, or - to read from stdin")]
+    pub file_name: String,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "auto",
+        help = "How the packets are stored. hex expects one packet per line, binary a single packet \
+        and pcap a capture of the UDP traffic to the display."
+    )]
+    pub input_format: InputFormat,
+
+    #[arg(
+        long,
+        default_value_t = 2342,
+        help = "UDP port the display listens on, to find its packets in a pcap capture"
+    )]
+    pub port: u16,
+
+    #[arg(long, help = "Do not draw the pixels of bitmaps")]
+    pub no_art: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum InputFormat {
+    Auto,
+    Hex,
+    Binary,
+    Pcap,
+}
+
 #[derive(clap::Parser, std::fmt::Debug, Clone)]
 pub struct ImageProcessingOptions {
     #[arg(long, help = "Disable histogram correction")]
@@ -168,6 +665,34 @@ pub struct ImageProcessingOptions {
     )]
     pub no_dither: bool,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value = "ostromoukhov",
+        help = "Dithering algorithm to use"
+    )]
+    pub dither: DitherAlgorithm,
+
+    #[arg(
+        long,
+        help = "Keep pixels stable between frames unless the input changes noticeably. \
+        Reduces flickering of still areas when streaming."
+    )]
+    pub temporal: bool,
+
+    #[arg(
+        long,
+        default_value_t = 24,
+        help = "How much the brightness of a pixel has to change before it is toggled in temporal mode"
+    )]
+    pub temporal_hysteresis: u8,
+
+    #[arg(
+        long,
+        help = "Set the brightness of each tile according to the image, which results in more shades of gray."
+    )]
+    pub tile_brightness: bool,
+
     #[arg(long, help = "Do not remove the spacers from the image.")]
     pub no_spacers: bool,
 
@@ -175,8 +700,166 @@ pub struct ImageProcessingOptions {
     pub no_aspect: bool,
 }
 
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct FrameDiffOptions {
+    #[arg(
+        long,
+        help = "Always send full frames instead of only the changed tiles."
+    )]
+    pub no_delta: bool,
+
+    #[arg(
+        long,
+        default_value_t = 0.5,
+        value_parser = parse_fraction,
+        help = "Send a full frame if more than this fraction of tiles changed"
+    )]
+    pub delta_threshold: f64,
+
+    #[arg(
+        long,
+        default_value_t = 60,
+        help = "Send a full frame after this many frames to recover from lost packets"
+    )]
+    pub keyframe_interval: usize,
+}
+
 #[derive(clap::Parser, std::fmt::Debug, Clone)]
 pub struct SendImageOptions {
     #[arg()]
     pub file_name: String,
 }
+
+#[derive(clap::Parser, std::fmt::Debug, Clone)]
+pub struct PlaybackOptions {
+    #[arg(
+        long,
+        default_value_t = 1.0,
+        value_parser = parse_positive_f64,
+        help = "Playback speed multiplier, e.g. 0.5 for half speed"
+    )]
+    pub speed: f64,
+
+    #[arg(
+        long,
+        value_parser = parse_positive_f64,
+        help = "Maximum frames per second to send. Cannot exceed the frame pacing of the display."
+    )]
+    pub max_fps: Option<f64>,
+
+    #[arg(
+        long = "loop",
+        value_name = "N",
+        num_args = 0..=1,
+        default_missing_value = "0",
+        help = "Play N times in total. Loops forever if N is omitted or 0."
+    )]
+    pub loop_count: Option<u32>,
+
+    #[arg(
+        long,
+        value_parser = parse_timestamp,
+        help = "Start playback at this timestamp, e.g. 90, 1:30 or 0:01:30.5"
+    )]
+    pub start: Option<Duration>,
+
+    #[arg(
+        long,
+        value_parser = parse_timestamp,
+        help = "Stop playback at this timestamp"
+    )]
+    pub end: Option<Duration>,
+
+    #[arg(long, help = "Stop playback after this many frames")]
+    pub frames: Option<usize>,
+}
+
+/// Parses timestamps in the format `[[hh:]mm:]ss[.fraction]`.
+fn parse_timestamp(value: &str) -> Result<Duration, String> {
+    let mut seconds = 0.0;
+    for (index, part) in value.split(':').enumerate() {
+        if index > 2 {
+            return Err("expected at most hours, minutes and seconds".to_string());
+        }
+        let part = part.parse::<f64>().map_err(|e| e.to_string())?;
+        if part < 0.0 || !part.is_finite() {
+            return Err("timestamp parts cannot be negative".to_string());
+        }
+        seconds = seconds * 60.0 + part;
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+/// Parses wall layouts in the format `<columns>x<rows>`.
+fn parse_wall_layout(value: &str) -> Result<WallLayout, String> {
+    let (columns, rows) = value
+        .split_once('x')
+        .ok_or("expected the format <columns>x<rows>, e.g. 2x1")?;
+    let columns = columns.parse::<usize>().map_err(|e| e.to_string())?;
+    let rows = rows.parse::<usize>().map_err(|e| e.to_string())?;
+    if columns == 0 || rows == 0 {
+        return Err("a wall needs at least one display".to_string());
+    }
+    Ok(WallLayout { columns, rows })
+}
+
+/// Parses regions of the display in the format `<x>,<y>,<width>x<height>`.
+fn parse_region(value: &str) -> Result<Region, String> {
+    let format = "expected the format <x>,<y>,<width>x<height>, e.g. 0,10,56x10";
+    let (position, size) = value.rsplit_once(',').ok_or(format)?;
+    let (x, y) = position.split_once(',').ok_or(format)?;
+    let (width, height) = size.split_once('x').ok_or(format)?;
+    let parse = |part: &str| part.parse::<usize>().map_err(|e| e.to_string());
+    let region = Region {
+        x: parse(x)?,
+        y: parse(y)?,
+        width: parse(width)?,
+        height: parse(height)?,
+    };
+    if region.width == 0 || region.height == 0 {
+        return Err("a region needs at least one character".to_string());
+    }
+    let fits = |start: usize, length: usize, available: usize| {
+        start
+            .checked_add(length)
+            .is_some_and(|end| end <= available)
+    };
+    if !fits(region.x, region.width, TILE_WIDTH) || !fits(region.y, region.height, TILE_HEIGHT) {
+        return Err(format!(
+            "the region does not fit on the display, which has {TILE_WIDTH}x{TILE_HEIGHT} characters"
+        ));
+    }
+    Ok(region)
+}
+
+/// Parses HTTP headers in the format `<name>:<value>`.
+fn parse_header(value: &str) -> Result<(String, String), String> {
+    let (name, value) = value
+        .split_once(':')
+        .ok_or("expected the format <name>:<value>")?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let seconds = parse_positive_f64(value)?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+/// Bounded, so speeds, rates and their reciprocals still fit into a [Duration].
+fn parse_positive_f64(value: &str) -> Result<f64, String> {
+    const MIN: f64 = 1e-3;
+    const MAX: f64 = 1e9;
+    match value.parse::<f64>() {
+        Ok(value) if (MIN..=MAX).contains(&value) => Ok(value),
+        Ok(_) => Err(format!("value has to be between {MIN} and {MAX}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(value) if (0.0..=1.0).contains(&value) => Ok(value),
+        Ok(_) => Err("value has to be between 0 and 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
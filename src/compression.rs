@@ -0,0 +1,123 @@
+use crate::{
+    cli::CompressionMode,
+    error::{Error, Result},
+};
+use log::{debug, info};
+use servicepoint::{CompressionCode, Packet, TypedCommand};
+use std::time::{Duration, Instant};
+
+/// How many commands are compressed with every codec before `auto` picks one.
+const AUTO_SAMPLES: usize = 10;
+
+/// Encodes commands into packets, compressing their payload as selected in the options.
+///
+/// With [CompressionMode::Auto], the first commands are compressed with every codec and the
+/// smallest result is sent. Afterward, the codec with the lowest cost is used, which is the time
+/// spent compressing plus the time needed to send the result at `byte_rate`.
+#[derive(Debug)]
+pub struct CompressionSelector {
+    code: Option<CompressionCode>,
+    byte_rate: f64,
+    trials: Vec<Trial>,
+    samples: usize,
+}
+
+#[derive(Debug)]
+struct Trial {
+    code: CompressionCode,
+    time: Duration,
+    bytes: usize,
+}
+
+impl CompressionSelector {
+    pub fn new(mode: CompressionMode, byte_rate: f64) -> Self {
+        let code = match mode {
+            CompressionMode::None => Some(CompressionCode::Uncompressed),
+            CompressionMode::Zlib => Some(CompressionCode::Zlib),
+            CompressionMode::Bzip2 => Some(CompressionCode::Bzip2),
+            CompressionMode::Lzma => Some(CompressionCode::Lzma),
+            CompressionMode::Zstd => Some(CompressionCode::Zstd),
+            CompressionMode::Auto => None,
+        };
+        debug!("using compression {mode:?}");
+        Self {
+            code,
+            byte_rate,
+            trials: CompressionCode::ALL
+                .iter()
+                .map(|code| Trial {
+                    code: *code,
+                    time: Duration::ZERO,
+                    bytes: 0,
+                })
+                .collect(),
+            samples: 0,
+        }
+    }
+
+    pub fn encode(&mut self, mut command: TypedCommand) -> Result<Packet> {
+        if !Self::is_compressible(&command) {
+            return Self::encode_with(&mut command, CompressionCode::Uncompressed);
+        }
+        match self.code {
+            Some(code) => Self::encode_with(&mut command, code),
+            None => self.try_all(command),
+        }
+    }
+
+    fn is_compressible(command: &TypedCommand) -> bool {
+        matches!(command, TypedCommand::Bitmap(_) | TypedCommand::BitVec(_))
+    }
+
+    fn encode_with(command: &mut TypedCommand, code: CompressionCode) -> Result<Packet> {
+        match command {
+            TypedCommand::Bitmap(command) => command.compression = code,
+            TypedCommand::BitVec(command) => command.compression = code,
+            _ => {}
+        }
+        Packet::try_from(&*command).map_err(|e| Error::Encode(format!("{e:?}")))
+    }
+
+    fn try_all(&mut self, mut command: TypedCommand) -> Result<Packet> {
+        let mut smallest: Option<Packet> = None;
+        for trial in &mut self.trials {
+            let start = Instant::now();
+            let packet = Self::encode_with(&mut command, trial.code)?;
+            trial.time += start.elapsed();
+            trial.bytes += packet.size();
+
+            if smallest
+                .as_ref()
+                .map_or(true, |smallest| packet.size() < smallest.size())
+            {
+                smallest = Some(packet);
+            }
+        }
+
+        self.samples += 1;
+        if self.samples == AUTO_SAMPLES {
+            self.choose();
+        }
+        Ok(smallest.expect("no compression codes available"))
+    }
+
+    fn choose(&mut self) {
+        let cost = |trial: &Trial| trial.time.as_secs_f64() + trial.bytes as f64 / self.byte_rate;
+        for trial in &self.trials {
+            debug!(
+                "{:?} compressed to {} bytes in {:?}, cost {:.3}",
+                trial.code,
+                trial.bytes,
+                trial.time,
+                cost(trial)
+            );
+        }
+        let best = self
+            .trials
+            .iter()
+            .min_by(|a, b| cost(a).total_cmp(&cost(b)))
+            .expect("no compression codes available");
+        info!("automatically selected {:?} compression", best.code);
+        self.code = Some(best.code);
+    }
+}
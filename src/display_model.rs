@@ -0,0 +1,126 @@
+use font8x8::{
+    BASIC_FONTS, BLOCK_FONTS, BOX_FONTS, GREEK_FONTS, LATIN_FONTS, MISC_FONTS, UnicodeFonts,
+};
+use log::trace;
+use servicepoint::{
+    BinaryOperation, BitVecCommand, Bitmap, BrightnessGrid, Grid, Origin, TILE_HEIGHT, TILE_SIZE,
+    TILE_WIDTH, Tiles, TypedCommand, Value, ValueGrid, cp437::cp437_to_char,
+};
+
+/// An in-memory mirror of the display, updated by applying the same commands the display would
+/// receive.
+///
+/// Text is drawn with a generic 8x8 font, so it only approximates the font of the real display.
+#[derive(Debug, Clone)]
+pub struct DisplayModel {
+    pub pixels: Bitmap,
+    pub brightness: BrightnessGrid,
+}
+
+impl Default for DisplayModel {
+    fn default() -> Self {
+        Self {
+            pixels: Bitmap::max_sized(),
+            brightness: BrightnessGrid::new(TILE_WIDTH, TILE_HEIGHT),
+        }
+    }
+}
+
+impl DisplayModel {
+    pub fn apply(&mut self, command: TypedCommand) {
+        match command {
+            TypedCommand::Clear(_) | TypedCommand::FadeOut(_) => self.pixels.fill(false),
+            TypedCommand::HardReset(_) => *self = Self::default(),
+            TypedCommand::Bitmap(command) => {
+                let origin = command.origin;
+                let bitmap = command.bitmap;
+                for y in 0..bitmap.height() {
+                    for x in 0..bitmap.width() {
+                        self.pixels
+                            .set_optional(origin.x + x, origin.y + y, bitmap.get(x, y));
+                    }
+                }
+            }
+            TypedCommand::BitVec(command) => self.apply_bitvec(command),
+            TypedCommand::CharGrid(command) => {
+                self.draw_text(&command.grid, command.origin, |character| character);
+            }
+            TypedCommand::Cp437Grid(command) => {
+                self.draw_text(&command.grid, command.origin, cp437_to_char);
+            }
+            TypedCommand::Brightness(command) => self.brightness.fill(command.brightness),
+            TypedCommand::BrightnessGrid(command) => {
+                let origin = command.origin;
+                let grid = command.grid;
+                for y in 0..grid.height() {
+                    for x in 0..grid.width() {
+                        self.brightness
+                            .set_optional(origin.x + x, origin.y + y, grid.get(x, y));
+                    }
+                }
+            }
+            #[allow(deprecated)]
+            TypedCommand::BitmapLegacy(_) => trace!("ignoring legacy bitmap command"),
+        }
+    }
+
+    /// The bits are written row by row starting at the pixel offset, wrapping at the end of each
+    /// row like on the real display.
+    fn apply_bitvec(&mut self, command: BitVecCommand) {
+        let width = self.pixels.width();
+        for (index, value) in command.bitvec.iter().by_vals().enumerate() {
+            let position = command.offset + index;
+            let (x, y) = (position % width, position / width);
+            if y >= self.pixels.height() {
+                break;
+            }
+
+            let old = self.pixels.get(x, y);
+            let new = match command.operation {
+                BinaryOperation::Overwrite => value,
+                BinaryOperation::And => old && value,
+                BinaryOperation::Or => old || value,
+                BinaryOperation::Xor => old != value,
+            };
+            self.pixels.set(x, y, new);
+        }
+    }
+
+    fn draw_text<T: Value>(
+        &mut self,
+        grid: &ValueGrid<T>,
+        origin: Origin<Tiles>,
+        to_char: impl Fn(T) -> char,
+    ) {
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                self.draw_char(origin.x + x, origin.y + y, to_char(grid.get(x, y)));
+            }
+        }
+    }
+
+    fn draw_char(&mut self, tile_x: usize, tile_y: usize, character: char) {
+        for (row, bits) in glyph(character).into_iter().enumerate() {
+            for column in 0..TILE_SIZE {
+                self.pixels.set_optional(
+                    tile_x * TILE_SIZE + column,
+                    tile_y * TILE_SIZE + row,
+                    bits & (1 << column) != 0,
+                );
+            }
+        }
+    }
+}
+
+/// Looks up the 8x8 glyph of a character. Each byte is one row, with the lowest bit on the left.
+fn glyph(character: char) -> [u8; 8] {
+    BASIC_FONTS
+        .get(character)
+        .or_else(|| LATIN_FONTS.get(character))
+        .or_else(|| BOX_FONTS.get(character))
+        .or_else(|| BLOCK_FONTS.get(character))
+        .or_else(|| GREEK_FONTS.get(character))
+        .or_else(|| MISC_FONTS.get(character))
+        .or_else(|| BASIC_FONTS.get('?'))
+        .expect("fallback glyph is missing")
+}
@@ -0,0 +1,101 @@
+use crate::{
+    cli::DumpFormat,
+    error::{Error, Result},
+};
+use servicepoint::{CommandCode, CompressionCode, Packet};
+use std::{fmt::Write as _, io::Write as _};
+
+/// Prints a packet to stdout, e.g. to debug what is sent to the display.
+pub fn dump_packet(packet: &Packet, format: DumpFormat) -> Result<()> {
+    let text = match format {
+        DumpFormat::Hex => format_hex(packet),
+        DumpFormat::Json => format_json(packet),
+    };
+    std::io::stdout()
+        .lock()
+        .write_all(text.as_bytes())
+        .map_err(|e| Error::io("failed to write packet dump", e))
+}
+
+/// The name of the command, or `None` for unknown command codes.
+pub fn command_name(packet: &Packet) -> Option<String> {
+    CommandCode::try_from(packet.header.command_code)
+        .ok()
+        .map(|code| format!("{code:?}"))
+}
+
+/// The compression of the payload, or `None` for commands without compression.
+pub fn compression(packet: &Packet) -> Option<CompressionCode> {
+    match CommandCode::try_from(packet.header.command_code).ok()? {
+        CommandCode::BitmapLinearWinUncompressed => Some(CompressionCode::Uncompressed),
+        CommandCode::BitmapLinearWinZlib => Some(CompressionCode::Zlib),
+        CommandCode::BitmapLinearWinBzip2 => Some(CompressionCode::Bzip2),
+        CommandCode::BitmapLinearWinLzma => Some(CompressionCode::Lzma),
+        CommandCode::BitmapLinearWinZstd => Some(CompressionCode::Zstd),
+        CommandCode::BitmapLinear
+        | CommandCode::BitmapLinearAnd
+        | CommandCode::BitmapLinearOr
+        | CommandCode::BitmapLinearXor => CompressionCode::try_from(packet.header.c).ok(),
+        _ => None,
+    }
+}
+
+fn payload(packet: &Packet) -> &[u8] {
+    packet.payload.as_deref().unwrap_or_default()
+}
+
+/// One line describing the header of a packet.
+pub fn summary(packet: &Packet) -> String {
+    let header = &packet.header;
+    format!(
+        "{} (0x{:04x}) a={} b={} c={} d={} compression={} payload={} bytes",
+        command_name(packet).as_deref().unwrap_or("Unknown"),
+        header.command_code,
+        header.a,
+        header.b,
+        header.c,
+        header.d,
+        compression(packet).map_or("-".to_string(), |code| format!("{code:?}")),
+        payload(packet).len()
+    )
+}
+
+/// The summary as a comment, followed by all bytes of the packet on one line, which is the
+/// format the inspect command reads.
+fn format_hex(packet: &Packet) -> String {
+    let bytes: Vec<u8> = packet.into();
+    let mut text = format!("# {}\n", summary(packet));
+    for (index, byte) in bytes.iter().enumerate() {
+        if index > 0 {
+            text.push(' ');
+        }
+        write!(text, "{byte:02x}").unwrap();
+    }
+    text.push('\n');
+    text
+}
+
+/// One JSON object per line. All values are numbers or known identifiers, so no escaping is
+/// needed.
+fn format_json(packet: &Packet) -> String {
+    let header = &packet.header;
+    let payload = payload(packet);
+    let optional_string =
+        |value: Option<String>| value.map_or("null".to_string(), |value| format!("\"{value}\""));
+    let mut hex = String::with_capacity(payload.len() * 2);
+    for byte in payload {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+    format!(
+        "{{\"command\":{},\"command_code\":{},\"a\":{},\"b\":{},\"c\":{},\"d\":{},\
+        \"compression\":{},\"payload_length\":{},\"payload\":\"{hex}\"}}\n",
+        optional_string(command_name(packet)),
+        header.command_code,
+        header.a,
+        header.b,
+        header.c,
+        header.d,
+        optional_string(compression(packet).map(|code| format!("{code:?}"))),
+        payload.len()
+    )
+}
@@ -0,0 +1,239 @@
+use crate::{
+    cli::{EmulatorOptions, RenderStyle},
+    display_model::DisplayModel,
+    error::{Error, Result},
+};
+use log::{debug, error, info, warn};
+use servicepoint::{Brightness, FRAME_PACING, Grid, Packet, TILE_SIZE, TypedCommand};
+use std::{
+    io::Write,
+    net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+    sync::mpsc::{RecvTimeoutError, Sender, channel},
+    thread,
+    time::Instant,
+};
+
+/// Receives commands like the real display and draws the result into the terminal.
+pub fn emulate(options: EmulatorOptions) -> Result<()> {
+    let (sender, receiver) = channel();
+
+    let socket = UdpSocket::bind(&options.bind).map_err(|e| {
+        Error::Connection(format!("failed to bind udp socket {}: {e}", options.bind))
+    })?;
+    info!("receiving udp packets on {}", options.bind);
+    let udp_sender = sender.clone();
+    thread::spawn(move || receive_udp(socket, udp_sender));
+
+    if let Some(address) = &options.websocket {
+        let listener = TcpListener::bind(address).map_err(|e| {
+            Error::Connection(format!("failed to bind websocket listener {address}: {e}"))
+        })?;
+        info!("accepting websocket connections on {address}");
+        let websocket_sender = sender.clone();
+        thread::spawn(move || accept_websockets(listener, websocket_sender));
+    }
+    drop(sender);
+
+    let mut display = DisplayModel::default();
+    let mut terminal = TerminalRenderer::new(options.render);
+    let mut last_render = Instant::now();
+    let mut dirty = true;
+    loop {
+        match receiver.recv_timeout(FRAME_PACING) {
+            Ok(command) => {
+                display.apply(command);
+                dirty = true;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(Error::Connection("stopped receiving packets".to_string()));
+            }
+        }
+
+        // the display also does not show updates faster than this
+        if dirty && last_render.elapsed() >= FRAME_PACING {
+            terminal.render(&display)?;
+            last_render = Instant::now();
+            dirty = false;
+        }
+    }
+}
+
+fn receive_udp(socket: UdpSocket, sender: Sender<TypedCommand>) {
+    let mut buffer = vec![0u8; u16::MAX as usize];
+    loop {
+        let (size, source) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(e) => {
+                error!("failed to receive udp packet: {e}");
+                return;
+            }
+        };
+        if let Some(command) = decode(&buffer[..size], source) {
+            if sender.send(command).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn accept_websockets(listener: TcpListener, sender: Sender<TypedCommand>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let sender = sender.clone();
+                thread::spawn(move || receive_websocket(stream, sender));
+            }
+            Err(e) => warn!("failed to accept websocket connection: {e}"),
+        }
+    }
+}
+
+fn receive_websocket(stream: TcpStream, sender: Sender<TypedCommand>) {
+    let source = match stream.peer_addr() {
+        Ok(source) => source,
+        Err(e) => {
+            warn!("failed to get websocket peer address: {e}");
+            return;
+        }
+    };
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("websocket handshake with {source} failed: {e}");
+            return;
+        }
+    };
+    info!("websocket client {source} connected");
+
+    loop {
+        match socket.read() {
+            Ok(tungstenite::Message::Binary(bytes)) => {
+                if let Some(command) = decode(&bytes, source) {
+                    if sender.send(command).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(tungstenite::Message::Close(_))
+            | Err(tungstenite::Error::Protocol(
+                tungstenite::error::ProtocolError::ResetWithoutClosingHandshake,
+            )) => break,
+            // pings are answered by tungstenite
+            Ok(_) => {}
+            Err(e) => {
+                warn!("websocket connection to {source} failed: {e}");
+                break;
+            }
+        }
+    }
+    info!("websocket client {source} disconnected");
+}
+
+fn decode(bytes: &[u8], source: SocketAddr) -> Option<TypedCommand> {
+    let packet = match Packet::try_from(bytes) {
+        Ok(packet) => packet,
+        Err(e) => {
+            warn!("received invalid packet from {source}: {e:?}");
+            return None;
+        }
+    };
+    match TypedCommand::try_from(packet) {
+        Ok(command) => {
+            debug!("received command from {source}: {command:?}");
+            Some(command)
+        }
+        Err(e) => {
+            warn!("received invalid command from {source}: {e:?}");
+            None
+        }
+    }
+}
+
+/// Draws the pixels using Unicode characters that fit multiple pixels into one cell.
+///
+/// The brightness of each tile is shown as a shade of gray.
+struct TerminalRenderer {
+    style: RenderStyle,
+    output: String,
+}
+
+impl TerminalRenderer {
+    fn new(style: RenderStyle) -> Self {
+        // clear the terminal once, later frames are drawn over the previous one
+        print!("\x1b[2J");
+        Self {
+            style,
+            output: String::new(),
+        }
+    }
+
+    fn render(&mut self, display: &DisplayModel) -> Result<()> {
+        let (cell_width, cell_height) = match self.style {
+            RenderStyle::Braille => (2, 4),
+            RenderStyle::HalfBlock => (1, 2),
+        };
+        let pixels = &display.pixels;
+
+        self.output.clear();
+        self.output.push_str("\x1b[H");
+        for cell_y in 0..pixels.height() / cell_height {
+            let mut color = None;
+            for cell_x in 0..pixels.width() / cell_width {
+                let (x, y) = (cell_x * cell_width, cell_y * cell_height);
+                // cells never span multiple tiles
+                let brightness = display.brightness.get(x / TILE_SIZE, y / TILE_SIZE);
+                let cell_color = Self::gray(brightness);
+                if color != Some(cell_color) {
+                    self.output.push_str(&format!("\x1b[38;5;{cell_color}m"));
+                    color = Some(cell_color);
+                }
+
+                let character = match self.style {
+                    RenderStyle::Braille => Self::braille(display, x, y),
+                    RenderStyle::HalfBlock => match (pixels.get(x, y), pixels.get(x, y + 1)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    },
+                };
+                self.output.push(character);
+            }
+            self.output.push_str("\x1b[0m\n");
+        }
+
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(self.output.as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(|e| Error::io("failed to write to terminal", e))
+    }
+
+    /// Maps the 2x4 pixels starting at `x`, `y` to the dots of a braille character.
+    fn braille(display: &DisplayModel, x: usize, y: usize) -> char {
+        const DOTS: [(usize, usize, u32); 8] = [
+            (0, 0, 0x01),
+            (0, 1, 0x02),
+            (0, 2, 0x04),
+            (1, 0, 0x08),
+            (1, 1, 0x10),
+            (1, 2, 0x20),
+            (0, 3, 0x40),
+            (1, 3, 0x80),
+        ];
+        let dots = DOTS
+            .iter()
+            .filter(|(dx, dy, _)| display.pixels.get(x + dx, y + dy))
+            .fold(0, |dots, (_, _, bit)| dots | bit);
+        char::from_u32(0x2800 + dots).expect("invalid braille character")
+    }
+
+    /// Maps the brightness levels to the grayscale ramp of the 256 color palette.
+    fn gray(brightness: Brightness) -> u8 {
+        const GRAY_START: u8 = 232;
+        const GRAY_STEPS: u8 = 23;
+        let level = u8::from(brightness);
+        GRAY_START + level * GRAY_STEPS / u8::from(Brightness::MAX)
+    }
+}
@@ -0,0 +1,85 @@
+use image::ImageError;
+use std::fmt::{Display, Formatter};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors that stop the program. Each kind has its own exit code, so scripts can tell them apart.
+///
+/// | code | kind                                  |
+/// |------|---------------------------------------|
+/// | 2    | invalid arguments                     |
+/// | 3    | I/O error, e.g. writing a file        |
+/// | 4    | display unreachable or sending failed |
+/// | 5    | input could not be decoded            |
+/// | 6    | command could not be encoded          |
+/// | 7    | unsupported format or platform        |
+/// | 8    | permission denied                     |
+///
+/// Panics still exit with code 101 and indicate a bug.
+#[derive(Debug)]
+pub enum Error {
+    /// Arguments that could not be checked by clap, e.g. a destination with an unknown scheme.
+    InvalidArguments(String),
+    Io {
+        context: String,
+        source: std::io::Error,
+    },
+    Connection(String),
+    Decode(String),
+    Encode(String),
+    UnsupportedFormat(String),
+    Permission(String),
+}
+
+impl Error {
+    pub fn io(context: impl Into<String>, source: std::io::Error) -> Self {
+        Self::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// Sorts errors of the image crate into the matching kind.
+    pub fn image(context: &str, error: ImageError) -> Self {
+        match error {
+            ImageError::IoError(source) => Self::io(context, source),
+            ImageError::Unsupported(e) => Self::UnsupportedFormat(format!("{context}: {e}")),
+            e => Self::Decode(format!("{context}: {e}")),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::InvalidArguments(_) => 2,
+            Self::Io { .. } => 3,
+            Self::Connection(_) => 4,
+            Self::Decode(_) => 5,
+            Self::Encode(_) => 6,
+            Self::UnsupportedFormat(_) => 7,
+            Self::Permission(_) => 8,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidArguments(message) => write!(f, "invalid arguments: {message}"),
+            Self::Io { context, source } => write!(f, "{context}: {source}"),
+            Self::Connection(message) => write!(f, "connection error: {message}"),
+            Self::Decode(message) => write!(f, "could not decode input: {message}"),
+            Self::Encode(message) => write!(f, "could not encode command: {message}"),
+            Self::UnsupportedFormat(message) => write!(f, "unsupported: {message}"),
+            Self::Permission(message) => write!(f, "permission denied: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{
+    cli::FileFormat,
+    display_model::DisplayModel,
+    error::{Error, Result},
+};
+use image::{
+    Delay, DynamicImage, Frame, GrayImage, Luma, RgbaImage,
+    codecs::gif::{GifEncoder, Repeat},
+};
+use log::{debug, error, info};
+use servicepoint::{Brightness, DataRef, FRAME_PACING, Grid, TILE_SIZE, TypedCommand};
+use std::{
+    fs::{File, create_dir_all},
+    io::Write,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// Applies commands to a [DisplayModel] instead of sending them, and writes the resulting frames
+/// into a directory.
+///
+/// A frame is written whenever the sender marks the end of one, e.g. after the brightness and
+/// the changed tiles of one video frame, so the same input always results in the same frames.
+/// Only the delays of gif frames are measured while sending.
+pub struct FileTransport {
+    directory: PathBuf,
+    format: FileFormat,
+    display: DisplayModel,
+    gif: Option<GifEncoder<File>>,
+    /// The last gif frame and when it was finished, as its delay is only known once the next
+    /// frame is finished.
+    gif_frame: Option<(RgbaImage, Instant)>,
+    /// the display changed since the last frame was written
+    pending: bool,
+    frames_written: usize,
+}
+
+impl FileTransport {
+    pub fn new(directory: &str, format: FileFormat) -> Result<Self> {
+        let directory = PathBuf::from(directory);
+        create_dir_all(&directory)
+            .map_err(|e| Error::io("failed to create output directory", e))?;
+
+        let gif = match format {
+            FileFormat::Gif => {
+                let file = File::create(directory.join("display.gif"))
+                    .map_err(|e| Error::io("failed to create gif file", e))?;
+                let mut encoder = GifEncoder::new(file);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(|e| Error::image("failed to write gif", e))?;
+                Some(encoder)
+            }
+            FileFormat::Png | FileFormat::Pbm => None,
+        };
+
+        info!("writing {format:?} frames to {}", directory.display());
+        Ok(Self {
+            directory,
+            format,
+            display: DisplayModel::default(),
+            gif,
+            gif_frame: None,
+            pending: false,
+            frames_written: 0,
+        })
+    }
+
+    pub fn send(&mut self, command: TypedCommand) {
+        self.display.apply(command);
+        self.pending = true;
+    }
+
+    /// Writes the state of the display after the commands sent since the last frame, unless
+    /// they did not change anything.
+    pub fn end_frame(&mut self) -> Result<()> {
+        if !self.pending {
+            return Ok(());
+        }
+        self.pending = false;
+        let index = self.frames_written;
+        self.frames_written += 1;
+        debug!("writing frame {index}");
+
+        match self.format {
+            FileFormat::Png => self
+                .render()
+                .save(self.directory.join(format!("frame-{index:05}.png")))
+                .map_err(|e| Error::image("failed to write png file", e)),
+            FileFormat::Pbm => self.write_pbm(index),
+            FileFormat::Gif => {
+                let image = DynamicImage::from(self.render()).into_rgba8();
+                match self.gif_frame.replace((image, Instant::now())) {
+                    Some((previous, finished)) => {
+                        self.write_gif_frame(previous, finished.elapsed())
+                    }
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Writes a gif frame that was shown for `duration`.
+    fn write_gif_frame(&mut self, image: RgbaImage, duration: Duration) -> Result<()> {
+        let delay = Delay::from_saturating_duration(duration.max(FRAME_PACING));
+        self.gif
+            .as_mut()
+            .expect("gif encoder missing")
+            .encode_frame(Frame::from_parts(image, 0, 0, delay))
+            .map_err(|e| Error::image("failed to write gif frame", e))
+    }
+
+    /// Draws the pixels with the gray level of the brightness of their tile.
+    fn render(&self) -> GrayImage {
+        let pixels = &self.display.pixels;
+        let max = u8::from(Brightness::MAX) as u32;
+        GrayImage::from_fn(pixels.width() as u32, pixels.height() as u32, |x, y| {
+            let (x, y) = (x as usize, y as usize);
+            if !pixels.get(x, y) {
+                return Luma([0]);
+            }
+            let level = u8::from(self.display.brightness.get(x / TILE_SIZE, y / TILE_SIZE));
+            Luma([(level as u32 * 255 / max) as u8])
+        })
+    }
+
+    /// PBM files contain the raw pixel bits, so lit pixels are black.
+    fn write_pbm(&self, index: usize) -> Result<()> {
+        let pixels = &self.display.pixels;
+        let mut file = File::create(self.directory.join(format!("frame-{index:05}.pbm")))
+            .map_err(|e| Error::io("failed to create pbm file", e))?;
+        write!(file, "P4\n{} {}\n", pixels.width(), pixels.height())
+            .and_then(|_| file.write_all(pixels.data_ref()))
+            .map_err(|e| Error::io("failed to write pbm file", e))
+    }
+}
+
+impl Drop for FileTransport {
+    fn drop(&mut self) {
+        let result = self.end_frame().and_then(|()| match self.gif_frame.take() {
+            Some((image, finished)) => self.write_gif_frame(image, finished.elapsed()),
+            None => Ok(()),
+        });
+        // errors cannot be returned from here anymore
+        if let Err(e) = result {
+            error!("failed to write last frame: {e}");
+        }
+        info!(
+            "wrote {} frames to {}",
+            self.frames_written,
+            self.directory.display()
+        );
+    }
+}
+
+impl std::fmt::Debug for FileTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileTransport")
+            .field("directory", &self.directory)
+            .field("format", &self.format)
+            .field("frames_written", &self.frames_written)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{cli::FrameDiffOptions, image_processing::ProcessedFrame};
+use log::{debug, trace};
+use servicepoint::{
+    Bitmap, BitmapCommand, BrightnessGrid, BrightnessGridCommand, CompressionCode, DataRef, Grid,
+    Origin, TILE_SIZE, TypedCommand,
+};
+
+/// Compares frames against the last sent frame and only sends the tiles that changed.
+///
+/// Full frames are sent for the first frame, when a lot of tiles changed and periodically as
+/// key frames, so lost packets do not leave the display out of sync forever.
+#[derive(Debug)]
+pub struct FrameDiffer {
+    options: FrameDiffOptions,
+    last_sent: Option<Bitmap>,
+    last_brightness: Option<BrightnessGrid>,
+    frames_since_keyframe: usize,
+}
+
+/// A rectangle in tile coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TileRect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl FrameDiffer {
+    pub fn new(options: FrameDiffOptions) -> Self {
+        debug!("Creating frame differ: {:?}", options);
+        Self {
+            options,
+            last_sent: None,
+            last_brightness: None,
+            frames_since_keyframe: 0,
+        }
+    }
+
+    /// Returns the commands needed to update the display from the last frame to `frame`.
+    ///
+    /// The brightness grid is only sent when it changed.
+    #[must_use]
+    pub fn diff(&mut self, frame: ProcessedFrame) -> Vec<TypedCommand> {
+        let mut commands = Vec::new();
+        let is_keyframe =
+            self.options.no_delta || self.frames_since_keyframe >= self.options.keyframe_interval;
+
+        if let Some(brightness) = frame.brightness {
+            if is_keyframe || self.last_brightness.as_ref() != Some(&brightness) {
+                commands.push(BrightnessGridCommand::from(brightness.clone()).into());
+                self.last_brightness = Some(brightness);
+            }
+        }
+
+        let bitmap = frame.bitmap;
+        let last_sent = match &self.last_sent {
+            Some(last_sent)
+                if !is_keyframe
+                    && last_sent.width() == bitmap.width()
+                    && last_sent.height() == bitmap.height() =>
+            {
+                last_sent
+            }
+            _ => {
+                commands.push(self.keyframe(bitmap).into());
+                return commands;
+            }
+        };
+
+        let tiles_x = bitmap.width() / TILE_SIZE;
+        let tiles_y = bitmap.height().div_ceil(TILE_SIZE);
+        let changed = Self::changed_tiles(last_sent, &bitmap, tiles_x, tiles_y);
+        let changed_count = changed.iter().filter(|changed| **changed).count();
+        let change_ratio = changed_count as f64 / changed.len().max(1) as f64;
+        if change_ratio > self.options.delta_threshold {
+            trace!("{changed_count} tiles changed, sending full frame");
+            commands.push(self.keyframe(bitmap).into());
+            return commands;
+        }
+
+        let rects = Self::merge_tiles(&changed, tiles_x, tiles_y);
+        trace!(
+            "{changed_count} tiles changed, sending {} rectangles",
+            rects.len()
+        );
+        commands.extend(
+            rects
+                .into_iter()
+                .map(|rect| Self::rect_command(&bitmap, rect).into()),
+        );
+
+        self.last_sent = Some(bitmap);
+        self.frames_since_keyframe += 1;
+        commands
+    }
+
+    fn keyframe(&mut self, bitmap: Bitmap) -> BitmapCommand {
+        self.frames_since_keyframe = 0;
+        self.last_sent = Some(bitmap.clone());
+        BitmapCommand {
+            origin: Origin::ZERO,
+            bitmap,
+            compression: CompressionCode::default(),
+        }
+    }
+
+    fn changed_tiles(old: &Bitmap, new: &Bitmap, tiles_x: usize, tiles_y: usize) -> Vec<bool> {
+        let row_bytes = new.width() / TILE_SIZE;
+        let old_data = old.data_ref();
+        let new_data = new.data_ref();
+
+        let mut changed = vec![false; tiles_x * tiles_y];
+        for y in 0..new.height() {
+            let row_start = y * row_bytes;
+            for tile_x in 0..tiles_x {
+                if old_data[row_start + tile_x] != new_data[row_start + tile_x] {
+                    changed[(y / TILE_SIZE) * tiles_x + tile_x] = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Merges horizontal runs of changed tiles, then merges runs with the same span in
+    /// consecutive rows.
+    fn merge_tiles(changed: &[bool], tiles_x: usize, tiles_y: usize) -> Vec<TileRect> {
+        let mut done = Vec::new();
+        let mut open: Vec<TileRect> = Vec::new();
+
+        for y in 0..tiles_y {
+            let row = &changed[y * tiles_x..(y + 1) * tiles_x];
+            let mut runs = Vec::new();
+            let mut x = 0;
+            while x < tiles_x {
+                if !row[x] {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < tiles_x && row[x] {
+                    x += 1;
+                }
+                runs.push((start, x - start));
+            }
+
+            let mut still_open = Vec::with_capacity(runs.len());
+            for (x, width) in runs {
+                match open
+                    .iter()
+                    .position(|rect| rect.x == x && rect.width == width)
+                {
+                    Some(index) => {
+                        let mut rect = open.swap_remove(index);
+                        rect.height += 1;
+                        still_open.push(rect);
+                    }
+                    None => still_open.push(TileRect {
+                        x,
+                        y,
+                        width,
+                        height: 1,
+                    }),
+                }
+            }
+            done.append(&mut open);
+            open = still_open;
+        }
+
+        done.append(&mut open);
+        done
+    }
+
+    fn rect_command(bitmap: &Bitmap, rect: TileRect) -> BitmapCommand {
+        let row_bytes = bitmap.width() / TILE_SIZE;
+        let pixel_y = rect.y * TILE_SIZE;
+        let pixel_height = (rect.height * TILE_SIZE).min(bitmap.height() - pixel_y);
+
+        let data = bitmap.data_ref();
+        let mut rect_data = Vec::with_capacity(rect.width * pixel_height);
+        for y in pixel_y..pixel_y + pixel_height {
+            let row_start = y * row_bytes + rect.x;
+            rect_data.extend_from_slice(&data[row_start..row_start + rect.width]);
+        }
+
+        BitmapCommand {
+            origin: Origin::new(rect.x * TILE_SIZE, pixel_y),
+            bitmap: Bitmap::load(rect.width * TILE_SIZE, pixel_height, &rect_data).unwrap(),
+            compression: CompressionCode::default(),
+        }
+    }
+}
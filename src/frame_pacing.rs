@@ -0,0 +1,115 @@
+use crate::cli::PlaybackOptions;
+use log::{debug, trace};
+use servicepoint::FRAME_PACING;
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// Schedules frames of a timed source (e.g. a video) in real time.
+///
+/// All deadlines are calculated relative to the start of playback, so slow frames do not cause
+/// drift. Frames that would exceed the maximum frame rate get dropped, and so do frames that are
+/// already late, unless `drop_late` is false.
+#[derive(Debug)]
+pub struct FramePacer {
+    speed: f64,
+    min_interval: Duration,
+    drop_late: bool,
+    loop_count: Option<u32>,
+    iterations: u32,
+    start: Option<Instant>,
+    last_shown: Option<Duration>,
+    dropped: usize,
+}
+
+impl FramePacer {
+    pub fn new(options: &PlaybackOptions, drop_late: bool) -> Self {
+        let min_interval = match options.max_fps {
+            Some(fps) => Duration::from_secs_f64(1.0 / fps).max(FRAME_PACING),
+            None => FRAME_PACING,
+        };
+        debug!(
+            "pacing frames with speed {} and at least {:?} between frames",
+            options.speed, min_interval
+        );
+        Self {
+            speed: options.speed,
+            min_interval,
+            drop_late,
+            loop_count: options.loop_count,
+            iterations: 0,
+            start: None,
+            last_shown: None,
+            dropped: 0,
+        }
+    }
+
+    /// Decides whether the frame at `position` (media time since the start of playback) should
+    /// be shown, returning the point in time it is due.
+    ///
+    /// The clock starts with the first scheduled frame.
+    #[must_use]
+    pub fn schedule(&mut self, position: Duration) -> Option<Instant> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let offset = position.div_f64(self.speed);
+        let due = start + offset;
+
+        if let Some(last_shown) = self.last_shown {
+            if offset < last_shown + self.min_interval {
+                trace!("dropping frame at {position:?} to keep frame rate");
+                self.dropped += 1;
+                return None;
+            }
+        }
+
+        if self.drop_late && Instant::now() > due + self.min_interval {
+            trace!("dropping frame at {position:?} because it is late");
+            self.dropped += 1;
+            return None;
+        }
+
+        self.last_shown = Some(offset);
+        Some(due)
+    }
+
+    /// Returns the point in time `position` is due, for sources where nothing may be dropped,
+    /// e.g. recorded packets that only contain the changes to earlier ones.
+    pub fn due(&mut self, position: Duration) -> Instant {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        start + position.div_f64(self.speed)
+    }
+
+    /// Blocks until the frame scheduled for `due` should be sent.
+    pub fn wait_until(due: Instant) {
+        sleep(due.saturating_duration_since(Instant::now()));
+    }
+
+    /// Blocks until `position` is reached, e.g. to show the last frame for its full duration.
+    pub fn finish(&self, position: Duration) {
+        if let Some(start) = self.start {
+            Self::wait_until(start + position.div_f64(self.speed));
+        }
+    }
+
+    /// Restarts the clock if the source should be played again according to the loop options.
+    #[must_use]
+    pub fn next_iteration(&mut self) -> bool {
+        self.iterations += 1;
+        let repeat = match self.loop_count {
+            None => false,
+            Some(0) => true,
+            Some(count) => self.iterations < count,
+        };
+        if repeat {
+            debug!("starting iteration {}", self.iterations + 1);
+            self.start = None;
+            self.last_shown = None;
+        }
+        repeat
+    }
+
+    pub fn dropped_frames(&self) -> usize {
+        self.dropped
+    }
+}
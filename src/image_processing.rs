@@ -1,54 +1,76 @@
 use crate::{
-    cli::ImageProcessingOptions,
-    ledwand_dither::{blur, histogram_correction, median_brightness, ostromoukhov_dither, sharpen},
+    cli::{DitherAlgorithm, ImageProcessingOptions},
+    ledwand_dither::{
+        ATKINSON, FLOYD_STEINBERG, Hysteresis, JARVIS_JUDICE_NINKE, SIERRA, ThresholdMap, blur,
+        error_diffusion_dither, histogram_correction, median_brightness, ordered_dither,
+        ostromoukhov_dither, sharpen, threshold_bitmap,
+    },
+    wall::WallLayout,
 };
 use fast_image_resize::{ResizeOptions, Resizer};
 use image::{DynamicImage, GrayImage};
 use log::{debug, trace};
-use servicepoint::{Bitmap, Grid, PIXEL_HEIGHT, PIXEL_WIDTH, TILE_HEIGHT, TILE_SIZE};
+use servicepoint::{Bitmap, Brightness, BrightnessGrid, Grid, PIXEL_WIDTH, TILE_HEIGHT, TILE_SIZE};
 use std::{default::Default, time::Instant};
 
+/// The result of processing one frame.
+#[derive(Debug, Clone)]
+pub struct ProcessedFrame {
+    pub bitmap: Bitmap,
+    /// Per-tile brightness, only calculated if enabled in the options.
+    pub brightness: Option<BrightnessGrid>,
+}
+
 #[derive(Debug)]
 pub struct ImageProcessingPipeline {
     options: ImageProcessingOptions,
     resizer: Resizer,
     render_size: (u32, u32),
+    previous: Option<Bitmap>,
 }
 
 const SPACER_HEIGHT: usize = TILE_SIZE / 2;
 
 impl ImageProcessingPipeline {
-    pub fn new(options: ImageProcessingOptions) -> Self {
+    /// Frames are rendered at the combined resolution of all displays of the wall.
+    pub fn new(options: ImageProcessingOptions, wall_layout: WallLayout) -> Self {
         debug!("Creating image pipeline: {:?}", options);
 
-        let height = PIXEL_HEIGHT
+        let tile_rows = TILE_HEIGHT * wall_layout.rows;
+        let height = tile_rows * TILE_SIZE
             + if options.no_spacers {
                 0
             } else {
-                SPACER_HEIGHT * (TILE_HEIGHT - 1)
+                SPACER_HEIGHT * (tile_rows - 1)
             };
 
         Self {
             options,
             resizer: Resizer::new(),
-            render_size: (PIXEL_WIDTH as u32, height as u32),
+            render_size: ((PIXEL_WIDTH * wall_layout.columns) as u32, height as u32),
+            previous: None,
         }
     }
 
     #[must_use]
-    pub fn process(&mut self, frame: DynamicImage) -> Bitmap {
+    pub fn process(&mut self, frame: DynamicImage) -> ProcessedFrame {
         let start_time = Instant::now();
 
         let frame = self.resize_grayscale(frame);
-        let frame = self.grayscale_processing(frame);
-        let mut result = self.grayscale_to_bitmap(frame);
+        let mut frame = self.grayscale_processing(frame);
+        let brightness = if self.options.tile_brightness {
+            Some(self.tile_brightness(&mut frame))
+        } else {
+            None
+        };
+        let mut bitmap = self.grayscale_to_bitmap(frame);
 
         if !self.options.no_spacers {
-            result = Self::remove_spacers(result);
+            bitmap = Self::remove_spacers(bitmap);
         }
 
         trace!("pipeline took {:?}", start_time.elapsed());
-        result
+        ProcessedFrame { bitmap, brightness }
     }
 
     fn resize_grayscale(&mut self, frame: DynamicImage) -> GrayImage {
@@ -96,15 +118,109 @@ impl ImageProcessingPipeline {
         orig
     }
 
-    fn grayscale_to_bitmap(&self, orig: GrayImage) -> Bitmap {
+    /// Calculates a brightness level for each tile from its average luminance.
+    ///
+    /// The pixels of each tile are amplified by the inverse of the tile brightness, which shifts
+    /// the dither bias so the tile stays as bright as before on average.
+    fn tile_brightness(&self, frame: &mut GrayImage) -> BrightnessGrid {
+        let start_time = Instant::now();
+
+        let max_level = u32::from(u8::from(Brightness::MAX));
+        let tile_row_height = if self.options.no_spacers {
+            TILE_SIZE
+        } else {
+            TILE_SIZE + SPACER_HEIGHT
+        };
+        let tiles_x = frame.width() as usize / TILE_SIZE;
+        let tiles_y = (frame.height() as usize).div_ceil(tile_row_height);
+        let mut grid = BrightnessGrid::new(tiles_x, tiles_y);
+
+        for tile_y in 0..tiles_y {
+            let first_row = (tile_y * tile_row_height) as u32;
+            let rows = first_row..(first_row + TILE_SIZE as u32).min(frame.height());
+            for tile_x in 0..tiles_x {
+                let first_col = (tile_x * TILE_SIZE) as u32;
+                let cols = first_col..first_col + TILE_SIZE as u32;
+
+                let mut sum = 0;
+                let mut count = 0;
+                for y in rows.clone() {
+                    for x in cols.clone() {
+                        sum += u32::from(frame.get_pixel(x, y).0[0]);
+                        count += 1;
+                    }
+                }
+
+                // round up, so the amplified pixels do not exceed the maximum on average
+                let level = (sum * max_level).div_ceil(count * u8::MAX as u32);
+                grid.set(tile_x, tile_y, Brightness::saturating_from(level as u8));
+                if level == 0 {
+                    continue;
+                }
+
+                for y in rows.clone() {
+                    for x in cols.clone() {
+                        let pixel = &mut frame.get_pixel_mut(x, y).0[0];
+                        let amplified = u32::from(*pixel) * max_level / level;
+                        *pixel = amplified.min(u8::MAX as u32) as u8;
+                    }
+                }
+            }
+        }
+
+        trace!("tile brightness took {:?}", start_time.elapsed());
+        grid
+    }
+
+    fn grayscale_to_bitmap(&mut self, orig: GrayImage) -> Bitmap {
         let start_time = Instant::now();
+
+        let previous = self
+            .previous
+            .as_ref()
+            .filter(|previous| {
+                previous.width() == orig.width() as usize
+                    && previous.height() == orig.height() as usize
+            })
+            .map(|previous| Hysteresis::new(previous, self.options.temporal_hysteresis));
+        let hysteresis = previous.as_ref();
+
         let result = if self.options.no_dither {
             let cutoff = median_brightness(&orig);
-            let bits = orig.iter().map(move |x| x > &cutoff).collect();
-            Bitmap::from_bitvec(orig.width() as usize, bits).unwrap()
+            threshold_bitmap(orig, cutoff, hysteresis)
         } else {
-            ostromoukhov_dither(orig, u8::MAX / 2)
+            let bias = u8::MAX / 2;
+            match self.options.dither {
+                DitherAlgorithm::Ostromoukhov => ostromoukhov_dither(orig, bias, hysteresis),
+                DitherAlgorithm::FloydSteinberg => {
+                    error_diffusion_dither(orig, &FLOYD_STEINBERG, bias, hysteresis)
+                }
+                DitherAlgorithm::Atkinson => {
+                    error_diffusion_dither(orig, &ATKINSON, bias, hysteresis)
+                }
+                DitherAlgorithm::JarvisJudiceNinke => {
+                    error_diffusion_dither(orig, &JARVIS_JUDICE_NINKE, bias, hysteresis)
+                }
+                DitherAlgorithm::Sierra => error_diffusion_dither(orig, &SIERRA, bias, hysteresis),
+                DitherAlgorithm::Bayer2x2 => {
+                    ordered_dither(orig, &ThresholdMap::bayer(2), hysteresis)
+                }
+                DitherAlgorithm::Bayer4x4 => {
+                    ordered_dither(orig, &ThresholdMap::bayer(4), hysteresis)
+                }
+                DitherAlgorithm::Bayer8x8 => {
+                    ordered_dither(orig, &ThresholdMap::bayer(8), hysteresis)
+                }
+                DitherAlgorithm::BlueNoise => {
+                    ordered_dither(orig, ThresholdMap::blue_noise(), hysteresis)
+                }
+            }
         };
+
+        if self.options.temporal {
+            self.previous = Some(result.clone());
+        }
+
         trace!("bitmap conversion took {:?}", start_time.elapsed());
         result
     }
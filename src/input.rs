@@ -0,0 +1,15 @@
+use crate::error::{Error, Result};
+use std::io::Read;
+
+/// Reads a whole file, or stdin if the file name is `-`.
+pub(crate) fn read_input(file_name: &str) -> Result<Vec<u8>> {
+    if file_name == "-" {
+        let mut data = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut data)
+            .map_err(|e| Error::io("failed to read stdin", e))?;
+        Ok(data)
+    } else {
+        std::fs::read(file_name).map_err(|e| Error::io(format!("failed to read {file_name}"), e))
+    }
+}
@@ -0,0 +1,322 @@
+use crate::{
+    cli::{InputFormat, InspectOptions},
+    dump::summary,
+    error::{Error, Result},
+    input::read_input,
+    pcap::{is_capture, read_udp_datagrams},
+};
+use data_encoding::HEXLOWER_PERMISSIVE;
+use log::{debug, info};
+use servicepoint::{Grid, PIXEL_WIDTH, Packet, TypedCommand, cp437::cp437_to_char};
+use std::{fmt::Write as _, io::Write as _};
+
+/// A packet as read from the input, before it is decoded.
+#[derive(Debug)]
+struct RawPacket {
+    /// Where the packet was found, e.g. its line or its time in a capture.
+    label: String,
+    bytes: Vec<u8>,
+}
+
+/// Decodes raw packets and prints which commands they contain and what they would draw.
+pub fn inspect(options: InspectOptions) -> Result<()> {
+    let data = read_input(&options.file_name)?;
+    let format = match options.input_format {
+        InputFormat::Auto => detect_format(&data),
+        format => format,
+    };
+    debug!("reading input as {format:?}");
+
+    let packets = match format {
+        InputFormat::Auto => unreachable!("format was detected"),
+        InputFormat::Hex => parse_hex(&data)?,
+        InputFormat::Binary => vec![RawPacket {
+            label: "packet".to_string(),
+            bytes: data,
+        }],
+        InputFormat::Pcap => read_udp_datagrams(&data, options.port)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, datagram)| RawPacket {
+                label: format!(
+                    "packet {} at {:.3}s from {}",
+                    index + 1,
+                    datagram.timestamp.as_secs_f64(),
+                    datagram.source
+                ),
+                bytes: datagram.bytes,
+            })
+            .collect(),
+    };
+
+    let mut invalid = 0;
+    let mut stdout = std::io::stdout().lock();
+    for packet in &packets {
+        let (text, valid) = describe_packet(packet, !options.no_art);
+        if !valid {
+            invalid += 1;
+        }
+        stdout
+            .write_all(text.as_bytes())
+            .map_err(|e| Error::io("failed to write description", e))?;
+    }
+
+    info!("inspected {} packets", packets.len());
+    if invalid > 0 {
+        return Err(Error::Decode(format!(
+            "{invalid} of {} packets are invalid",
+            packets.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Packets always contain bytes that are not hex digits, as the command codes are small numbers.
+fn detect_format(data: &[u8]) -> InputFormat {
+    if is_capture(data) {
+        InputFormat::Pcap
+    } else if data.split(|&byte| byte == b'\n').all(|line| {
+        line.starts_with(b"#")
+            || line
+                .iter()
+                .all(|byte| byte.is_ascii_hexdigit() || byte.is_ascii_whitespace())
+    }) {
+        InputFormat::Hex
+    } else {
+        InputFormat::Binary
+    }
+}
+
+/// One packet per line, whitespace between the digits is ignored. Lines starting with `#` are
+/// comments.
+fn parse_hex(data: &[u8]) -> Result<Vec<RawPacket>> {
+    let text = std::str::from_utf8(data)
+        .map_err(|e| Error::Decode(format!("hex input is not valid text: {e}")))?;
+    let mut packets = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let digits: String = line.split_whitespace().collect();
+        let bytes = HEXLOWER_PERMISSIVE
+            .decode(digits.as_bytes())
+            .map_err(|e| Error::Decode(format!("invalid hex on line {}: {e}", index + 1)))?;
+        packets.push(RawPacket {
+            label: format!("packet on line {}", index + 1),
+            bytes,
+        });
+    }
+    Ok(packets)
+}
+
+/// Returns the description and whether the packet contains a valid command.
+fn describe_packet(packet: &RawPacket, art: bool) -> (String, bool) {
+    let mut text = format!("{}, {} bytes\n", packet.label, packet.bytes.len());
+    let valid = match Packet::try_from(packet.bytes.as_slice()) {
+        Ok(decoded) => {
+            writeln!(text, "{}", summary(&decoded)).unwrap();
+            match TypedCommand::try_from(decoded) {
+                Ok(command) => {
+                    describe_command(&mut text, command, art);
+                    true
+                }
+                Err(e) => {
+                    writeln!(text, "invalid command: {e:?}").unwrap();
+                    false
+                }
+            }
+        }
+        Err(e) => {
+            writeln!(text, "invalid packet: {e:?}").unwrap();
+            false
+        }
+    };
+    text.push('\n');
+    (text, valid)
+}
+
+fn describe_command(text: &mut String, command: TypedCommand, art: bool) {
+    match command {
+        TypedCommand::Clear(_) => text.push_str("clear all pixels\n"),
+        TypedCommand::HardReset(_) => text.push_str("hard reset\n"),
+        TypedCommand::FadeOut(_) => text.push_str("fade out\n"),
+        TypedCommand::Brightness(command) => {
+            let level = u8::from(command.brightness);
+            writeln!(text, "set brightness of all tiles to {level}").unwrap();
+        }
+        TypedCommand::BrightnessGrid(command) => {
+            let grid = command.grid;
+            write_size(
+                text,
+                "set brightness of",
+                &grid,
+                "tiles",
+                (command.origin.x, command.origin.y),
+            );
+            for y in 0..grid.height() {
+                for x in 0..grid.width() {
+                    write!(text, "{:3}", u8::from(grid.get(x, y))).unwrap();
+                }
+                text.push('\n');
+            }
+        }
+        TypedCommand::Bitmap(command) => {
+            let bitmap = command.bitmap;
+            write_size(
+                text,
+                "draw",
+                &bitmap,
+                "pixels",
+                (command.origin.x, command.origin.y),
+            );
+            if art {
+                for y in 0..bitmap.height() {
+                    text.extend((0..bitmap.width()).map(|x| pixel_char(bitmap.get(x, y))));
+                    text.push('\n');
+                }
+            }
+        }
+        TypedCommand::BitVec(command) => {
+            let length = command.bitvec.len();
+            writeln!(
+                text,
+                "{:?} {length} pixels starting at pixel {} ({}, {})",
+                command.operation,
+                command.offset,
+                command.offset % PIXEL_WIDTH,
+                command.offset / PIXEL_WIDTH
+            )
+            .unwrap();
+            if art && length > 0 {
+                write_bitvec(text, command.offset, command.bitvec.iter().by_vals());
+            }
+        }
+        TypedCommand::CharGrid(command) => {
+            let grid = command.grid;
+            write_size(
+                text,
+                "write",
+                &grid,
+                "characters",
+                (command.origin.x, command.origin.y),
+            );
+            write_text(text, &grid, |character| character);
+        }
+        TypedCommand::Cp437Grid(command) => {
+            let grid = command.grid;
+            write_size(
+                text,
+                "write",
+                &grid,
+                "CP437 characters",
+                (command.origin.x, command.origin.y),
+            );
+            write_text(text, &grid, cp437_to_char);
+        }
+        #[allow(deprecated)]
+        TypedCommand::BitmapLegacy(_) => text.push_str("legacy bitmap, ignored by the display\n"),
+    }
+}
+
+/// Writes what a grid command does, with the origin in the unit of the grid.
+fn write_size<T>(
+    text: &mut String,
+    action: &str,
+    grid: &impl Grid<T>,
+    unit: &str,
+    (x, y): (usize, usize),
+) {
+    writeln!(
+        text,
+        "{action} {}x{} {unit} at ({x}, {y})",
+        grid.width(),
+        grid.height()
+    )
+    .unwrap();
+}
+
+/// Draws the bits in rows as wide as the display, like the display applies them.
+fn write_bitvec(text: &mut String, offset: usize, bits: impl Iterator<Item = bool>) {
+    let mut column = offset % PIXEL_WIDTH;
+    text.extend((0..column).map(|_| ' '));
+    for bit in bits {
+        text.push(pixel_char(bit));
+        column += 1;
+        if column == PIXEL_WIDTH {
+            text.push('\n');
+            column = 0;
+        }
+    }
+    if column != 0 {
+        text.push('\n');
+    }
+}
+
+fn write_text<T: Copy>(text: &mut String, grid: &impl Grid<T>, to_char: impl Fn(T) -> char) {
+    for y in 0..grid.height() {
+        text.extend((0..grid.width()).map(|x| {
+            let character = to_char(grid.get(x, y));
+            // control characters would garble the output
+            if character.is_control() {
+                '?'
+            } else {
+                character
+            }
+        }));
+        text.push('\n');
+    }
+}
+
+fn pixel_char(on: bool) -> char {
+    if on { '#' } else { '.' }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_hex_with_whitespace_and_comments() {
+        let hex = b"# clear\n0002 0000\t0000 0000 0000\r\n\n  00 02 00 00 00 00 00 00 00 00  \n";
+        assert!(matches!(detect_format(hex), InputFormat::Hex));
+        assert!(matches!(
+            detect_format(b"\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00"),
+            InputFormat::Binary
+        ));
+        assert!(matches!(
+            detect_format(b"\xd4\xc3\xb2\xa1\x02\x00\x04\x00"),
+            InputFormat::Pcap
+        ));
+    }
+
+    #[test]
+    fn hex_packets_are_read_per_line() {
+        let hex = b"# clear\n0002 0000\t0000 0000 0000\r\n\n  00 0B 00 00 00 00 00 00 00 00  \n";
+        let packets = parse_hex(hex).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].label, "packet on line 2");
+        assert_eq!(packets[0].bytes, [0, 2, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packets[1].label, "packet on line 4");
+        assert_eq!(packets[1].bytes, [0, 0x0b, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn invalid_hex_names_the_line() {
+        let Err(Error::Decode(message)) = parse_hex(b"0002\n000\n") else {
+            panic!("odd number of digits was accepted");
+        };
+        assert!(message.contains("line 2"), "{message}");
+    }
+
+    #[test]
+    fn describes_commands() {
+        let packets = parse_hex(b"0002 0000 0000 0000 0000\n0002 00\n").unwrap();
+        let (text, valid) = describe_packet(&packets[0], false);
+        assert!(valid);
+        assert!(text.contains("clear all pixels"), "{text}");
+        let (text, valid) = describe_packet(&packets[1], false);
+        assert!(!valid);
+        assert!(text.contains("invalid packet"), "{text}");
+    }
+}
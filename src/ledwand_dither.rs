@@ -2,7 +2,8 @@
 
 use image::GrayImage;
 use log::debug;
-use servicepoint::{Bitmap, DisplayBitVec, PIXEL_HEIGHT};
+use servicepoint::{Bitmap, DisplayBitVec, Grid, PIXEL_HEIGHT};
+use std::{sync::OnceLock, time::Instant};
 
 type GrayHistogram = [usize; 256];
 
@@ -164,7 +165,55 @@ fn sharpen_inner_pixels(source: &GrayImage, destination: &mut GrayImage) {
     }
 }
 
-pub(crate) fn ostromoukhov_dither(source: GrayImage, bias: u8) -> Bitmap {
+/// Shifts the threshold of each pixel towards its state in the previous frame, so small changes in
+/// the input do not toggle pixels back and forth.
+pub(crate) struct Hysteresis<'t> {
+    previous: &'t Bitmap,
+    amount: u8,
+}
+
+impl<'t> Hysteresis<'t> {
+    pub(crate) fn new(previous: &'t Bitmap, amount: u8) -> Self {
+        Self { previous, amount }
+    }
+
+    #[inline]
+    fn threshold(&self, position: usize, threshold: u8) -> u8 {
+        let width = self.previous.width();
+        if self.previous.get(position % width, position / width) {
+            threshold.saturating_sub(self.amount)
+        } else {
+            threshold.saturating_add(self.amount)
+        }
+    }
+}
+
+#[inline]
+fn apply_hysteresis(hysteresis: Option<&Hysteresis>, position: usize, threshold: u8) -> u8 {
+    match hysteresis {
+        Some(hysteresis) => hysteresis.threshold(position, threshold),
+        None => threshold,
+    }
+}
+
+pub(crate) fn threshold_bitmap(
+    source: GrayImage,
+    cutoff: u8,
+    hysteresis: Option<&Hysteresis>,
+) -> Bitmap {
+    let bits = source
+        .iter()
+        .enumerate()
+        .map(|(position, value)| *value > apply_hysteresis(hysteresis, position, cutoff))
+        .collect();
+    Bitmap::from_bitvec(source.width() as usize, bits).unwrap()
+}
+
+pub(crate) fn ostromoukhov_dither(
+    source: GrayImage,
+    bias: u8,
+    hysteresis: Option<&Hysteresis>,
+) -> Bitmap {
     let width = source.width();
     let height = source.height();
     assert_eq!(width % 8, 0);
@@ -184,7 +233,7 @@ pub(crate) fn ostromoukhov_dither(source: GrayImage, bias: u8) -> Bitmap {
                     width as usize,
                     last_row,
                     1,
-                    bias,
+                    apply_hysteresis(hysteresis, x, bias),
                 );
             }
         } else {
@@ -196,7 +245,7 @@ pub(crate) fn ostromoukhov_dither(source: GrayImage, bias: u8) -> Bitmap {
                     width as usize,
                     last_row,
                     -1,
-                    bias,
+                    apply_hysteresis(hysteresis, x, bias),
                 );
             }
         }
@@ -260,6 +309,310 @@ fn gray_to_bit(old_pixel: u8, bias: u8) -> (bool, u8) {
     (destination_value, error)
 }
 
+/// Weights for distributing the quantization error of a pixel to its neighbors.
+///
+/// Offsets are relative to the current pixel in scan direction and get mirrored on odd rows.
+pub(crate) struct DiffusionKernel {
+    weights: &'static [(isize, usize, i32)],
+    divisor: i32,
+}
+
+pub(crate) const FLOYD_STEINBERG: DiffusionKernel = DiffusionKernel {
+    weights: &[(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)],
+    divisor: 16,
+};
+
+/// Only diffuses 3/4 of the error, which results in more contrast.
+pub(crate) const ATKINSON: DiffusionKernel = DiffusionKernel {
+    weights: &[
+        (1, 0, 1),
+        (2, 0, 1),
+        (-1, 1, 1),
+        (0, 1, 1),
+        (1, 1, 1),
+        (0, 2, 1),
+    ],
+    divisor: 8,
+};
+
+pub(crate) const JARVIS_JUDICE_NINKE: DiffusionKernel = DiffusionKernel {
+    weights: &[
+        (1, 0, 7),
+        (2, 0, 5),
+        (-2, 1, 3),
+        (-1, 1, 5),
+        (0, 1, 7),
+        (1, 1, 5),
+        (2, 1, 3),
+        (-2, 2, 1),
+        (-1, 2, 3),
+        (0, 2, 5),
+        (1, 2, 3),
+        (2, 2, 1),
+    ],
+    divisor: 48,
+};
+
+pub(crate) const SIERRA: DiffusionKernel = DiffusionKernel {
+    weights: &[
+        (1, 0, 5),
+        (2, 0, 3),
+        (-2, 1, 2),
+        (-1, 1, 4),
+        (0, 1, 5),
+        (1, 1, 4),
+        (2, 1, 2),
+        (-1, 2, 2),
+        (0, 2, 3),
+        (1, 2, 2),
+    ],
+    divisor: 32,
+};
+
+pub(crate) fn error_diffusion_dither(
+    source: GrayImage,
+    kernel: &DiffusionKernel,
+    bias: u8,
+    hysteresis: Option<&Hysteresis>,
+) -> Bitmap {
+    let width = source.width() as usize;
+    let height = source.height() as usize;
+    assert_eq!(width % 8, 0);
+
+    let mut values: Vec<i32> = source.into_raw().into_iter().map(i32::from).collect();
+    let mut destination = DisplayBitVec::repeat(false, values.len());
+
+    for y in 0..height {
+        let direction = if y % 2 == 0 { 1 } else { -1 };
+        for step in 0..width {
+            let x = if direction == 1 {
+                step
+            } else {
+                width - 1 - step
+            };
+            let position = y * width + x;
+
+            let old_value = values[position].clamp(u8::MIN.into(), u8::MAX.into());
+            let threshold = apply_hysteresis(hysteresis, position, bias);
+            let destination_value = old_value > threshold.into();
+            destination.set(position, destination_value);
+
+            let error = if destination_value {
+                old_value - i32::from(u8::MAX)
+            } else {
+                old_value
+            };
+            for &(dx, dy, weight) in kernel.weights {
+                let target_x = x as isize + dx * direction;
+                let target_y = y + dy;
+                if target_x < 0 || target_x >= width as isize || target_y >= height {
+                    continue;
+                }
+                values[target_y * width + target_x as usize] += error * weight / kernel.divisor;
+            }
+        }
+    }
+
+    Bitmap::from_bitvec(width, destination).unwrap()
+}
+
+/// A square matrix of thresholds that gets tiled over the image.
+#[derive(Debug)]
+pub(crate) struct ThresholdMap {
+    size: usize,
+    thresholds: Vec<u8>,
+}
+
+const BLUE_NOISE_SIZE: usize = 32;
+const BLUE_NOISE_SIGMA: f32 = 1.5;
+
+impl ThresholdMap {
+    /// Creates a Bayer matrix. `size` has to be a power of two.
+    pub(crate) fn bayer(size: usize) -> Self {
+        assert!(size.is_power_of_two());
+        let mut ranks = vec![0usize];
+        let mut current_size = 1;
+        while current_size < size {
+            let next_size = current_size * 2;
+            let mut next = vec![0; next_size * next_size];
+            for y in 0..next_size {
+                for x in 0..next_size {
+                    let quadrant = match (x / current_size, y / current_size) {
+                        (0, 0) => 0,
+                        (1, 0) => 2,
+                        (0, _) => 3,
+                        _ => 1,
+                    };
+                    let rank = ranks[(y % current_size) * current_size + x % current_size];
+                    next[y * next_size + x] = 4 * rank + quadrant;
+                }
+            }
+            ranks = next;
+            current_size = next_size;
+        }
+        Self::from_ranks(size, &ranks)
+    }
+
+    /// Returns a blue noise matrix, which is generated on first use.
+    pub(crate) fn blue_noise() -> &'static Self {
+        static BLUE_NOISE: OnceLock<ThresholdMap> = OnceLock::new();
+        BLUE_NOISE.get_or_init(|| {
+            let start_time = Instant::now();
+            let ranks = VoidAndCluster::new(BLUE_NOISE_SIZE, BLUE_NOISE_SIGMA).ranks();
+            debug!("generating blue noise took {:?}", start_time.elapsed());
+            Self::from_ranks(BLUE_NOISE_SIZE, &ranks)
+        })
+    }
+
+    fn from_ranks(size: usize, ranks: &[usize]) -> Self {
+        let count = size * size;
+        let thresholds = ranks
+            .iter()
+            .map(|rank| ((2 * rank + 1) * u8::MAX as usize / (2 * count)) as u8)
+            .collect();
+        Self { size, thresholds }
+    }
+
+    #[inline]
+    pub(crate) fn threshold(&self, x: usize, y: usize) -> u8 {
+        self.thresholds[(y % self.size) * self.size + x % self.size]
+    }
+}
+
+pub(crate) fn ordered_dither(
+    source: GrayImage,
+    threshold_map: &ThresholdMap,
+    hysteresis: Option<&Hysteresis>,
+) -> Bitmap {
+    let width = source.width() as usize;
+    assert_eq!(width % 8, 0);
+
+    let bits = source
+        .enumerate_pixels()
+        .map(|(x, y, pixel)| {
+            let (x, y) = (x as usize, y as usize);
+            let threshold = threshold_map.threshold(x, y);
+            pixel.0[0] > apply_hysteresis(hysteresis, y * width + x, threshold)
+        })
+        .collect();
+    Bitmap::from_bitvec(width, bits).unwrap()
+}
+
+/// Generates blue noise using the void-and-cluster method by Robert Ulichney.
+struct VoidAndCluster {
+    size: usize,
+    kernel: Vec<f32>,
+    pattern: Vec<bool>,
+    energy: Vec<f32>,
+}
+
+impl VoidAndCluster {
+    fn new(size: usize, sigma: f32) -> Self {
+        let count = size * size;
+        let kernel = (0..count)
+            .map(|index| {
+                let dx = (index % size).min(size - index % size) as f32;
+                let dy = (index / size).min(size - index / size) as f32;
+                (-(dx * dx + dy * dy) / (2. * sigma * sigma)).exp()
+            })
+            .collect();
+        Self {
+            size,
+            kernel,
+            pattern: vec![false; count],
+            energy: vec![0.; count],
+        }
+    }
+
+    fn ranks(mut self) -> Vec<usize> {
+        let count = self.size * self.size;
+        let initial_points = count / 10;
+
+        // deterministic pseudo-random starting pattern
+        let mut state: u32 = 0x2342_2342;
+        let mut points = 0;
+        while points < initial_points {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let index = state as usize % count;
+            if !self.pattern[index] {
+                self.toggle(index);
+                points += 1;
+            }
+        }
+
+        // move points from the tightest clusters into the largest voids until stable
+        loop {
+            let cluster = self.tightest_cluster();
+            self.toggle(cluster);
+            let void = self.largest_void();
+            self.toggle(void);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let initial_pattern = self.pattern.clone();
+        let initial_energy = self.energy.clone();
+        let mut ranks = vec![0; count];
+
+        for rank in (0..initial_points).rev() {
+            let cluster = self.tightest_cluster();
+            self.toggle(cluster);
+            ranks[cluster] = rank;
+        }
+
+        self.pattern = initial_pattern;
+        self.energy = initial_energy;
+        for rank in initial_points..count {
+            let void = self.largest_void();
+            self.toggle(void);
+            ranks[void] = rank;
+        }
+
+        ranks
+    }
+
+    fn toggle(&mut self, index: usize) {
+        let sign = if self.pattern[index] { -1. } else { 1. };
+        self.pattern[index] = !self.pattern[index];
+
+        let (x, y) = (index % self.size, index / self.size);
+        for (target, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (target % self.size + self.size - x) % self.size;
+            let dy = (target / self.size + self.size - y) % self.size;
+            *energy += sign * self.kernel[dy * self.size + dx];
+        }
+    }
+
+    fn tightest_cluster(&self) -> usize {
+        self.find_extreme_energy(true, |a, b| a > b)
+    }
+
+    fn largest_void(&self) -> usize {
+        self.find_extreme_energy(false, |a, b| a < b)
+    }
+
+    fn find_extreme_energy(
+        &self,
+        pattern_value: bool,
+        is_better: impl Fn(f32, f32) -> bool,
+    ) -> usize {
+        let mut result = None;
+        for (index, energy) in self.energy.iter().enumerate() {
+            if self.pattern[index] != pattern_value {
+                continue;
+            }
+            match result {
+                Some((_, best)) if !is_better(*energy, best) => {}
+                _ => result = Some((index, *energy)),
+            }
+        }
+        result.expect("pattern cannot be empty or full").0
+    }
+}
+
 const ERROR_DIFFUSION_MATRIX: [[i16; 3]; 256] = [
     [0, 1, 0],
     [1, 0, 0],
@@ -1,48 +1,98 @@
 use crate::{
     brightness::{brightness, brightness_set},
-    cli::{Cli, Mode},
+    cli::{Cli, DisplayMode, Mode},
+    emulator::emulate,
+    error::Result,
+    inspect::inspect,
     pixels::{pixels, pixels_off},
+    replay::replay,
     text::text,
     transport::Transport,
 };
 use clap::Parser;
-use log::debug;
+use log::{debug, error};
 use servicepoint::{Brightness, HardResetCommand};
 
+mod animation;
+mod ansi;
 mod brightness;
+mod charset;
 mod cli;
+mod compression;
+mod display_model;
+mod dump;
+mod emulator;
+mod error;
+mod file_transport;
+mod frame_diff;
+mod frame_pacing;
 mod image_processing;
+mod input;
+mod inspect;
 mod ledwand_dither;
+mod pcap;
 mod pixels;
+#[cfg(unix)]
+mod pty;
+mod rate_limit;
+mod recording;
+mod replay;
+mod screen;
+mod statistics;
+#[cfg(unix)]
+mod stream_exec;
 mod stream_stdin;
+mod stream_video;
 mod stream_window;
 mod text;
+mod text_layout;
 mod transport;
+mod wall;
+mod websocket;
 
 fn main() {
     let cli = Cli::parse();
     init_logging(cli.verbose);
     debug!("running with arguments: {:?}", cli);
 
-    let transport = Transport::connect(cli.transport, &cli.destination);
-    debug!("connection established: {:#?}", transport);
+    if let Err(e) = run(cli) {
+        error!("{e}");
+        std::process::exit(e.exit_code());
+    }
+}
 
-    execute_mode(cli.command, transport);
+fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Mode::Display(mode) => {
+            let transport = Transport::connect(&cli.transport_options)?;
+            debug!("connection established: {:#?}", transport);
+            execute_mode(mode, transport)
+        }
+        // these modes receive or read packets instead of sending them
+        Mode::Emulate { emulator_options } => emulate(emulator_options),
+        Mode::Inspect { inspect_options } => inspect(inspect_options),
+    }
 }
 
-pub fn execute_mode(mode: Mode, connection: Transport) {
+pub fn execute_mode(mode: DisplayMode, connection: Transport) -> Result<()> {
     match mode {
-        Mode::Reset { force } => {
+        DisplayMode::Reset { force } => {
             if force {
-                connection.send_command(HardResetCommand).unwrap()
+                connection.send_command(HardResetCommand)
             } else {
-                brightness_set(&connection, Brightness::MAX);
-                pixels_off(&connection);
+                brightness_set(&connection, Brightness::MAX)?;
+                pixels_off(&connection)
             }
         }
-        Mode::Pixels { pixel_command } => pixels(&connection, pixel_command),
-        Mode::Brightness { brightness_command } => brightness(&connection, brightness_command),
-        Mode::Text { text_command } => text(&connection, text_command),
+        DisplayMode::Pixels { pixel_command } => pixels(&connection, pixel_command),
+        DisplayMode::Brightness { brightness_command } => {
+            brightness(&connection, brightness_command)
+        }
+        DisplayMode::Text { text_command } => text(&connection, text_command),
+        DisplayMode::Replay {
+            replay_options,
+            playback_options,
+        } => replay(&connection, replay_options, playback_options),
     }
 }
 
@@ -0,0 +1,389 @@
+use crate::error::{Error, Result};
+use log::{debug, warn};
+use std::{collections::HashMap, net::IpAddr, time::Duration};
+
+/// The magic number of pcap files with timestamps in microseconds.
+const MAGIC_MICROSECONDS: u32 = 0xa1b2c3d4;
+/// The magic number of pcap files with timestamps in nanoseconds.
+const MAGIC_NANOSECONDS: u32 = 0xa1b23c4d;
+/// pcapng files start with the block type of their section header.
+const PCAPNG_MAGIC: &[u8; 4] = b"\x0a\x0d\x0d\x0a";
+const FILE_HEADER_SIZE: usize = 24;
+const RECORD_HEADER_SIZE: usize = 16;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const PROTOCOL_UDP: u8 = 17;
+const UDP_HEADER_SIZE: usize = 8;
+
+/// The payload of a UDP datagram found in a capture.
+#[derive(Debug)]
+pub struct Datagram {
+    /// Relative to the first packet of the capture.
+    pub timestamp: Duration,
+    pub source: IpAddr,
+    pub bytes: Vec<u8>,
+}
+
+/// Whether the data looks like a pcap or pcapng file.
+pub fn is_capture(data: &[u8]) -> bool {
+    let Some(magic) = data.get(..4) else {
+        return false;
+    };
+    let magic: [u8; 4] = magic.try_into().unwrap();
+    magic == *PCAPNG_MAGIC
+        || [MAGIC_MICROSECONDS, MAGIC_NANOSECONDS]
+            .iter()
+            .any(|&known| u32::from_le_bytes(magic) == known || u32::from_be_bytes(magic) == known)
+}
+
+/// Extracts the payloads of all UDP datagrams sent to `port`, e.g. the packets a client sent to
+/// the display.
+///
+/// Fragmented IPv4 datagrams are reassembled, as uncompressed bitmaps do not fit into a single
+/// Ethernet frame. Only classic pcap files are supported, not pcapng.
+pub fn read_udp_datagrams(data: &[u8], port: u16) -> Result<Vec<Datagram>> {
+    if data.starts_with(PCAPNG_MAGIC) {
+        return Err(Error::UnsupportedFormat(
+            "pcapng captures, convert to pcap with `editcap -F pcap`".to_string(),
+        ));
+    }
+    if data.len() < FILE_HEADER_SIZE {
+        return Err(Error::Decode("capture is too short".to_string()));
+    }
+
+    let magic: [u8; 4] = data[..4].try_into().unwrap();
+    let (big_endian, nanoseconds) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+        (MAGIC_MICROSECONDS, _) => (false, false),
+        (MAGIC_NANOSECONDS, _) => (false, true),
+        (_, MAGIC_MICROSECONDS) => (true, false),
+        (_, MAGIC_NANOSECONDS) => (true, true),
+        _ => return Err(Error::Decode("not a pcap capture".to_string())),
+    };
+    let read_u32 = |bytes: &[u8]| {
+        let bytes = bytes[..4].try_into().unwrap();
+        if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    };
+
+    let link_type = read_u32(&data[20..]);
+    if ![
+        LINKTYPE_NULL,
+        LINKTYPE_ETHERNET,
+        LINKTYPE_RAW,
+        LINKTYPE_LINUX_SLL,
+        LINKTYPE_LINUX_SLL2,
+    ]
+    .contains(&link_type)
+    {
+        return Err(Error::UnsupportedFormat(format!(
+            "captures with link type {link_type}"
+        )));
+    }
+
+    let mut datagrams = Vec::new();
+    let mut fragments = HashMap::new();
+    let mut start = None;
+    let mut truncated = 0;
+    let mut rest = &data[FILE_HEADER_SIZE..];
+    while !rest.is_empty() {
+        if rest.len() < RECORD_HEADER_SIZE {
+            warn!("capture ends with a truncated packet");
+            break;
+        }
+        let (header, body) = rest.split_at(RECORD_HEADER_SIZE);
+        let seconds = read_u32(header) as u64;
+        let fraction = read_u32(&header[4..]);
+        let captured_length = read_u32(&header[8..]) as usize;
+        let original_length = read_u32(&header[12..]) as usize;
+        if body.len() < captured_length {
+            warn!("capture ends with a truncated packet");
+            break;
+        }
+        let frame = &body[..captured_length];
+        rest = &body[captured_length..];
+
+        let timestamp = Duration::from_secs(seconds)
+            + if nanoseconds {
+                Duration::from_nanos(fraction as u64)
+            } else {
+                Duration::from_micros(fraction as u64)
+            };
+        let start = *start.get_or_insert(timestamp);
+        if captured_length < original_length {
+            truncated += 1;
+            continue;
+        }
+
+        let Some((source, segment)) =
+            link_payload(link_type, frame).and_then(|packet| ip_payload(packet, &mut fragments))
+        else {
+            continue;
+        };
+        if let Some(bytes) = udp_payload(&segment, port) {
+            datagrams.push(Datagram {
+                timestamp: timestamp.saturating_sub(start),
+                source,
+                bytes: bytes.to_vec(),
+            });
+        }
+    }
+
+    if truncated > 0 {
+        warn!("skipped {truncated} packets that were truncated by the capture");
+    }
+    if !fragments.is_empty() {
+        warn!(
+            "skipped {} datagrams with missing fragments",
+            fragments.len()
+        );
+    }
+    debug!("found {} datagrams to port {port}", datagrams.len());
+    Ok(datagrams)
+}
+
+/// The IP packet inside a frame of the link layer, if it contains one.
+fn link_payload(link_type: u32, frame: &[u8]) -> Option<&[u8]> {
+    let read_u16 = |offset: usize| {
+        frame
+            .get(offset..offset + 2)
+            .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
+    };
+    let (ether_type, offset) = match link_type {
+        LINKTYPE_NULL => return frame.get(4..),
+        LINKTYPE_RAW => return Some(frame),
+        LINKTYPE_LINUX_SLL => (read_u16(14)?, 16),
+        LINKTYPE_LINUX_SLL2 => (read_u16(0)?, 20),
+        LINKTYPE_ETHERNET => match read_u16(12)? {
+            ETHERTYPE_VLAN => (read_u16(16)?, 18),
+            ether_type => (ether_type, 14),
+        },
+        _ => return None,
+    };
+    match ether_type {
+        ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => frame.get(offset..),
+        _ => None,
+    }
+}
+
+/// Fragments of an IPv4 datagram, identified by source, destination and identification.
+type FragmentKey = ([u8; 4], [u8; 4], u16);
+
+#[derive(Debug, Default)]
+struct Fragments {
+    parts: Vec<(usize, Vec<u8>)>,
+    /// Known once the last fragment arrived.
+    length: Option<usize>,
+}
+
+/// The sender and UDP segment of an IP packet. Fragments are kept until the datagram is
+/// complete.
+fn ip_payload(
+    packet: &[u8],
+    fragments: &mut HashMap<FragmentKey, Fragments>,
+) -> Option<(IpAddr, Vec<u8>)> {
+    match packet.first()? >> 4 {
+        4 => {
+            let header_length = (packet[0] & 0x0f) as usize * 4;
+            let total_length = u16::from_be_bytes(packet.get(2..4)?.try_into().unwrap()) as usize;
+            if packet.len() < header_length.max(20) || packet[9] != PROTOCOL_UDP {
+                return None;
+            }
+            let source: [u8; 4] = packet[12..16].try_into().unwrap();
+            let destination: [u8; 4] = packet[16..20].try_into().unwrap();
+            let body = packet.get(header_length..total_length.min(packet.len()))?;
+
+            let identification = u16::from_be_bytes(packet[4..6].try_into().unwrap());
+            let flags = u16::from_be_bytes(packet[6..8].try_into().unwrap());
+            let more_fragments = flags & 0x2000 != 0;
+            let offset = (flags & 0x1fff) as usize * 8;
+            if !more_fragments && offset == 0 {
+                return Some((source.into(), body.to_vec()));
+            }
+
+            let key = (source, destination, identification);
+            let datagram = fragments.entry(key).or_default();
+            datagram.parts.push((offset, body.to_vec()));
+            if !more_fragments {
+                datagram.length = Some(offset + body.len());
+            }
+            let received: usize = datagram.parts.iter().map(|(_, part)| part.len()).sum();
+            if datagram.length != Some(received) {
+                return None;
+            }
+
+            let mut datagram = fragments.remove(&key).expect("fragments disappeared");
+            datagram.parts.sort_by_key(|(offset, _)| *offset);
+            let segment = datagram.parts.into_iter().flat_map(|(_, part)| part);
+            Some((source.into(), segment.collect()))
+        }
+        6 => {
+            // extension headers, including fragments, are not supported
+            if *packet.get(6)? != PROTOCOL_UDP {
+                return None;
+            }
+            let payload_length = u16::from_be_bytes(packet.get(4..6)?.try_into().unwrap()) as usize;
+            let source: [u8; 16] = packet.get(8..24)?.try_into().unwrap();
+            let body = packet.get(40..(40 + payload_length).min(packet.len()))?;
+            Some((source.into(), body.to_vec()))
+        }
+        _ => None,
+    }
+}
+
+/// The payload of a UDP segment, if it was sent to `port`.
+fn udp_payload(segment: &[u8], port: u16) -> Option<&[u8]> {
+    let destination_port = u16::from_be_bytes(segment.get(2..4)?.try_into().unwrap());
+    let length = u16::from_be_bytes(segment.get(4..6)?.try_into().unwrap()) as usize;
+    if destination_port != port || length < UDP_HEADER_SIZE {
+        return None;
+    }
+    segment.get(UDP_HEADER_SIZE..length.min(segment.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PORT: u16 = 2342;
+
+    fn capture(link_type: u32, frames: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend(MAGIC_MICROSECONDS.to_le_bytes());
+        data.extend(2u16.to_le_bytes());
+        data.extend(4u16.to_le_bytes());
+        data.extend([0; 8]);
+        data.extend(65535u32.to_le_bytes());
+        data.extend(link_type.to_le_bytes());
+        for (seconds, microseconds, frame) in frames {
+            data.extend(seconds.to_le_bytes());
+            data.extend(microseconds.to_le_bytes());
+            data.extend((frame.len() as u32).to_le_bytes());
+            data.extend((frame.len() as u32).to_le_bytes());
+            data.extend(frame);
+        }
+        data
+    }
+
+    fn udp(port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut segment = Vec::new();
+        segment.extend(40000u16.to_be_bytes());
+        segment.extend(port.to_be_bytes());
+        segment.extend(((UDP_HEADER_SIZE + payload.len()) as u16).to_be_bytes());
+        segment.extend([0; 2]);
+        segment.extend(payload);
+        segment
+    }
+
+    /// An IPv4 packet from 10.0.0.1, `fragment` contains the flags and the offset.
+    fn ipv4(identification: u16, fragment: u16, body: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0];
+        packet.extend(((20 + body.len()) as u16).to_be_bytes());
+        packet.extend(identification.to_be_bytes());
+        packet.extend(fragment.to_be_bytes());
+        packet.extend([64, PROTOCOL_UDP, 0, 0]);
+        packet.extend([10, 0, 0, 1]);
+        packet.extend([10, 0, 0, 2]);
+        packet.extend(body);
+        packet
+    }
+
+    fn ethernet(packet: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0; 12];
+        frame.extend(ETHERTYPE_IPV4.to_be_bytes());
+        frame.extend(packet);
+        frame
+    }
+
+    #[test]
+    fn detects_captures() {
+        assert!(is_capture(&capture(LINKTYPE_RAW, &[])));
+        assert!(is_capture(b"\x0a\x0d\x0d\x0a\x1c\x00\x00\x00"));
+        assert!(!is_capture(b"0002 0000"));
+        assert!(!is_capture(b"\xa1"));
+    }
+
+    #[test]
+    fn udp_datagrams_to_the_port() {
+        let data = capture(
+            LINKTYPE_ETHERNET,
+            &[
+                (10, 500_000, ethernet(&ipv4(1, 0, &udp(PORT, b"first")))),
+                (11, 0, ethernet(&ipv4(2, 0, &udp(53, b"other port")))),
+                (12, 0, ethernet(&ipv4(3, 0, &udp(PORT, b"second")))),
+            ],
+        );
+        let datagrams = read_udp_datagrams(&data, PORT).unwrap();
+        assert_eq!(datagrams.len(), 2);
+        assert_eq!(datagrams[0].bytes, b"first");
+        assert_eq!(datagrams[0].timestamp, Duration::ZERO);
+        assert_eq!(datagrams[0].source, IpAddr::from([10, 0, 0, 1]));
+        assert_eq!(datagrams[1].bytes, b"second");
+        assert_eq!(datagrams[1].timestamp, Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn fragmented_datagrams_are_reassembled() {
+        let payload: Vec<u8> = (0..40).collect();
+        let segment = udp(PORT, &payload);
+        let (first, second) = segment.split_at(24);
+        const MORE_FRAGMENTS: u16 = 0x2000;
+        let data = capture(
+            LINKTYPE_RAW,
+            &[
+                // fragments can arrive in any order
+                (0, 0, ipv4(7, 24 / 8, second)),
+                (0, 1, ipv4(7, MORE_FRAGMENTS, first)),
+                // never completed
+                (0, 2, ipv4(8, MORE_FRAGMENTS, first)),
+            ],
+        );
+        let datagrams = read_udp_datagrams(&data, PORT).unwrap();
+        assert_eq!(datagrams.len(), 1);
+        assert_eq!(datagrams[0].bytes, payload);
+    }
+
+    #[test]
+    fn truncated_records_end_the_capture() {
+        let mut data = capture(LINKTYPE_RAW, &[(0, 0, ipv4(1, 0, &udp(PORT, b"kept")))]);
+        data.extend([0; RECORD_HEADER_SIZE - 1]);
+        let datagrams = read_udp_datagrams(&data, PORT).unwrap();
+        assert_eq!(datagrams.len(), 1);
+        assert_eq!(datagrams[0].bytes, b"kept");
+
+        let data = capture(LINKTYPE_RAW, &[(0, 0, ipv4(1, 0, &udp(PORT, b"cut off")))]);
+        let datagrams = read_udp_datagrams(&data[..data.len() - 1], PORT).unwrap();
+        assert!(datagrams.is_empty());
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        let pcapng = b"\x0a\x0d\x0d\x0a\x1c\x00\x00\x00\x4d\x3c\x2b\x1a";
+        assert!(matches!(
+            read_udp_datagrams(pcapng, PORT),
+            Err(Error::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            read_udp_datagrams(&[0; FILE_HEADER_SIZE], PORT),
+            Err(Error::Decode(_))
+        ));
+        assert!(matches!(
+            read_udp_datagrams(&capture(LINKTYPE_RAW, &[])[..10], PORT),
+            Err(Error::Decode(_))
+        ));
+        assert!(matches!(
+            read_udp_datagrams(&capture(147, &[]), PORT),
+            Err(Error::UnsupportedFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::error::{Error, Result};
+use log::debug;
+use std::{
+    fs::File,
+    io::ErrorKind,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::process::CommandExt,
+    },
+    process::{Child, Command, Stdio},
+};
+
+/// A program running in a pseudo terminal.
+#[derive(Debug)]
+pub struct Pty {
+    /// Reading returns the output of the program, writing sends it input.
+    pub master: File,
+    pub child: Child,
+}
+
+impl Pty {
+    pub fn spawn(command: &[String], width: usize, height: usize, term: &str) -> Result<Self> {
+        let (program, arguments) = command
+            .split_first()
+            .ok_or_else(|| Error::InvalidArguments("missing command".to_string()))?;
+
+        let size = libc::winsize {
+            ws_row: height as u16,
+            ws_col: width as u16,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let mut master = -1;
+        let mut slave = -1;
+        // SAFETY: the pointers are valid for the duration of the call
+        let result = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                &size,
+            )
+        };
+        if result != 0 {
+            return Err(Error::io(
+                "failed to open pseudo terminal",
+                std::io::Error::last_os_error(),
+            ));
+        }
+        // SAFETY: openpty returned two new file descriptors, which are owned from now on
+        let (master, slave) =
+            unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+        // the program must not inherit the master side, or it would never see the terminal close
+        // SAFETY: only changes flags of a valid file descriptor
+        unsafe { libc::fcntl(master.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+
+        let stdio = |fd: &OwnedFd| {
+            fd.try_clone()
+                .map(Stdio::from)
+                .map_err(|e| Error::io("failed to set up pseudo terminal", e))
+        };
+        let mut process = Command::new(program);
+        process
+            .args(arguments)
+            .env("TERM", term)
+            .stdin(stdio(&slave)?)
+            .stdout(stdio(&slave)?)
+            .stderr(stdio(&slave)?);
+        // SAFETY: only async-signal-safe functions are called between fork and exec
+        unsafe {
+            process.pre_exec(|| {
+                // a new session with the pseudo terminal as its controlling terminal, so
+                // programs get signals like SIGINT from it
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = process.spawn().map_err(|e| match e.kind() {
+            ErrorKind::NotFound => Error::InvalidArguments(format!("command not found: {program}")),
+            _ => Error::io(format!("failed to run {program}"), e),
+        })?;
+        debug!("started {program} with pid {}", child.id());
+
+        Ok(Self {
+            master: File::from(master),
+            child,
+        })
+    }
+}
+
+/// Puts a terminal into raw mode, so key presses are passed on immediately and unchanged, until
+/// this is dropped.
+#[derive(Debug)]
+pub struct RawMode {
+    fd: i32,
+    original: libc::termios,
+}
+
+impl RawMode {
+    pub fn enable(terminal: &impl AsRawFd) -> Result<Self> {
+        let fd = terminal.as_raw_fd();
+        // SAFETY: termios is plain data, which tcgetattr fills in
+        let mut original = unsafe { std::mem::zeroed() };
+        // SAFETY: the pointer is valid for the duration of the call
+        if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+            return Err(Error::io(
+                "failed to get terminal attributes",
+                std::io::Error::last_os_error(),
+            ));
+        }
+        let mut raw = original;
+        // SAFETY: the pointers are valid for the duration of the calls
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(fd, libc::TCSANOW, &raw) != 0 {
+                return Err(Error::io(
+                    "failed to set terminal attributes",
+                    std::io::Error::last_os_error(),
+                ));
+            }
+        }
+        Ok(Self { fd, original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: the pointer is valid for the duration of the call
+        unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.original) };
+    }
+}
@@ -0,0 +1,118 @@
+use crate::cli::RateLimitOptions;
+use log::{debug, trace};
+use servicepoint::{FRAME_PACING, Header, PIXEL_COUNT, TILE_HEIGHT, TILE_WIDTH};
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// Packets a display can receive per [FRAME_PACING], e.g. the brightness and a few changed
+/// regions of one frame.
+const FRAME_BUDGET_PACKETS: f64 = 16.0;
+/// Bytes a display can receive per [FRAME_PACING]: one uncompressed frame with brightness.
+const FRAME_BUDGET_BYTES: f64 =
+    (2 * size_of::<Header>() + PIXEL_COUNT / 8 + TILE_WIDTH * TILE_HEIGHT) as f64;
+
+/// Limits how many packets and bytes are sent to one display, so its receiver is not flooded
+/// with more than it can handle.
+///
+/// Both limits are token buckets holding the budget of one [FRAME_PACING] interval, so short
+/// bursts (like the packets of one frame) are sent without delay.
+#[derive(Debug)]
+pub struct RateLimiter {
+    packets: TokenBucket,
+    bytes: TokenBucket,
+}
+
+impl RateLimiter {
+    /// Returns `None` if rate limiting is disabled.
+    pub fn new(options: &RateLimitOptions) -> Option<Self> {
+        if options.no_rate_limit {
+            return None;
+        }
+
+        let packets = options
+            .max_packet_rate
+            .unwrap_or(FRAME_BUDGET_PACKETS / FRAME_PACING.as_secs_f64());
+        let bytes = byte_rate(options);
+        debug!("limiting to {packets:.0} packets and {bytes:.0} bytes per second");
+        Some(Self {
+            packets: TokenBucket::new(packets),
+            bytes: TokenBucket::new(bytes),
+        })
+    }
+
+    /// Blocks until a packet of `size` bytes may be sent, returning how long that took.
+    pub fn wait(&mut self, size: usize) -> Duration {
+        let size = size as f64;
+        let mut waited = Duration::ZERO;
+        loop {
+            let now = Instant::now();
+            self.packets.refill(now);
+            self.bytes.refill(now);
+
+            let delay = self.packets.delay(1.0).max(self.bytes.delay(size));
+            if delay.is_zero() {
+                self.packets.take(1.0);
+                self.bytes.take(size);
+                if !waited.is_zero() {
+                    trace!("rate limit delayed packet by {waited:?}");
+                }
+                return waited;
+            }
+
+            sleep(delay);
+            waited += delay;
+        }
+    }
+}
+
+/// Bytes per second allowed by the options. This is also used to weigh size against time when
+/// choosing a compression, even if rate limiting is disabled.
+pub fn byte_rate(options: &RateLimitOptions) -> f64 {
+    options
+        .max_byte_rate
+        .unwrap_or(FRAME_BUDGET_BYTES / FRAME_PACING.as_secs_f64())
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    /// tokens per second
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        let capacity = rate * FRAME_PACING.as_secs_f64();
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Returns how long it takes until `cost` tokens are available. Costs larger than the
+    /// capacity only need a full bucket, the missing tokens are paid for afterward.
+    fn delay(&self, cost: f64) -> Duration {
+        let missing = cost.min(self.capacity) - self.tokens;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.rate)
+        }
+    }
+
+    fn take(&mut self, cost: f64) {
+        self.tokens -= cost;
+    }
+}
@@ -0,0 +1,170 @@
+use crate::error::{Error, Result};
+use log::{debug, info, warn};
+use std::{
+    fs::File,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+/// Recordings start with this, followed by the version.
+const MAGIC: &[u8; 4] = b"SPRC";
+const VERSION: u8 = 1;
+/// Each packet is stored with its timestamp in microseconds and its length.
+const RECORD_HEADER_SIZE: usize = size_of::<u64>() + size_of::<u32>();
+
+/// Writes packets into a recording, together with the time they were sent relative to the first
+/// packet.
+///
+/// Every packet is written immediately, so a recording stays usable when the program is
+/// interrupted.
+#[derive(Debug)]
+pub struct Recorder {
+    file: File,
+    start: Option<Instant>,
+    packets: usize,
+}
+
+impl Recorder {
+    pub fn create(file_name: &str) -> Result<Self> {
+        let mut file =
+            File::create(file_name).map_err(|e| Error::io("failed to create recording file", e))?;
+        file.write_all(MAGIC)
+            .and_then(|_| file.write_all(&[VERSION]))
+            .map_err(|e| Error::io("failed to write recording", e))?;
+        info!("recording packets to {file_name}");
+        Ok(Self {
+            file,
+            start: None,
+            packets: 0,
+        })
+    }
+
+    pub fn record(&mut self, packet: &[u8]) -> Result<()> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let timestamp = start.elapsed().as_micros() as u64;
+
+        let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + packet.len());
+        record.extend_from_slice(&timestamp.to_le_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(packet);
+        self.file
+            .write_all(&record)
+            .map_err(|e| Error::io("failed to write recording", e))?;
+        self.packets += 1;
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        info!("recorded {} packets", self.packets);
+    }
+}
+
+#[derive(Debug)]
+pub struct RecordedPacket {
+    pub timestamp: Duration,
+    pub bytes: Vec<u8>,
+}
+
+/// Reads all packets of a recording. A truncated last packet, e.g. from an interrupted
+/// recording, is skipped.
+pub fn read_recording(file_name: &str) -> Result<Vec<RecordedPacket>> {
+    let data =
+        std::fs::read(file_name).map_err(|e| Error::io("failed to read recording file", e))?;
+    let header_size = MAGIC.len() + 1;
+    if data.len() < header_size || &data[..MAGIC.len()] != MAGIC {
+        return Err(Error::Decode(format!("{file_name} is not a recording")));
+    }
+    let version = data[MAGIC.len()];
+    if version != VERSION {
+        return Err(Error::UnsupportedFormat(format!(
+            "recording version {version}"
+        )));
+    }
+
+    let mut packets = Vec::new();
+    let mut rest = &data[header_size..];
+    while !rest.is_empty() {
+        if rest.len() < RECORD_HEADER_SIZE {
+            warn!("recording ends with a truncated packet");
+            break;
+        }
+        let (header, body) = rest.split_at(RECORD_HEADER_SIZE);
+        let timestamp = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let length = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+        if body.len() < length {
+            warn!("recording ends with a truncated packet");
+            break;
+        }
+
+        packets.push(RecordedPacket {
+            timestamp: Duration::from_micros(timestamp),
+            bytes: body[..length].to_vec(),
+        });
+        rest = &body[length..];
+    }
+
+    debug!("read {} packets from {file_name}", packets.len());
+    Ok(packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "servicepoint-cli-{}-{name}.sprc",
+            std::process::id()
+        ));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn recorded_packets_are_read_back() {
+        let file_name = temp_file("round-trip");
+        let packets: [&[u8]; 3] = [b"first", b"", &[0xff; 1000]];
+        {
+            let mut recorder = Recorder::create(&file_name).unwrap();
+            for packet in packets {
+                recorder.record(packet).unwrap();
+            }
+        }
+        // an interrupted recording
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&file_name)
+            .unwrap();
+        file.write_all(&0u64.to_le_bytes()).unwrap();
+        file.write_all(&10u32.to_le_bytes()).unwrap();
+        file.write_all(b"cut").unwrap();
+        drop(file);
+
+        let recorded = read_recording(&file_name).unwrap();
+        std::fs::remove_file(&file_name).unwrap();
+        let bytes: Vec<_> = recorded
+            .iter()
+            .map(|packet| packet.bytes.as_slice())
+            .collect();
+        assert_eq!(bytes, packets);
+        assert_eq!(recorded[0].timestamp, Duration::ZERO);
+        assert!(
+            recorded
+                .windows(2)
+                .all(|pair| pair[0].timestamp <= pair[1].timestamp)
+        );
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let file_name = temp_file("other");
+        std::fs::write(&file_name, b"SPRC\x02").unwrap();
+        let version = read_recording(&file_name);
+        std::fs::write(&file_name, b"PNG").unwrap();
+        let other = read_recording(&file_name);
+        std::fs::remove_file(&file_name).unwrap();
+        assert!(matches!(version, Err(Error::UnsupportedFormat(_))));
+        assert!(matches!(other, Err(Error::Decode(_))));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{
+    cli::{PlaybackOptions, ReplayOptions},
+    error::{Error, Result},
+    frame_pacing::FramePacer,
+    recording::{RecordedPacket, read_recording},
+    transport::Transport,
+};
+use log::{info, warn};
+use servicepoint::Packet;
+use std::time::Duration;
+
+/// Recorded packets closer together than this belong to the same frame, e.g. the brightness and
+/// the changed tiles of one video frame.
+///
+/// The packets of a frame are sent right after each other, while frames are at least
+/// [servicepoint::FRAME_PACING] (30 ms) apart, as faster frames are not shown by the display.
+/// Half of that tolerates delays in either direction without merging or splitting frames.
+const FRAME_GAP: Duration = Duration::from_millis(15);
+
+/// Sends the packets of a recording with their original timing.
+///
+/// Unlike frames of a video, no packets get dropped when sending falls behind, as later packets
+/// may only contain the changes to earlier ones. For the same reason, packets before `--start`
+/// are sent right away instead of being skipped.
+pub(crate) fn replay(
+    connection: &Transport,
+    options: ReplayOptions,
+    playback_options: PlaybackOptions,
+) -> Result<()> {
+    if playback_options.max_fps.is_some() {
+        return Err(Error::InvalidArguments(
+            "--max-fps cannot be used for replays, as recorded packets cannot be dropped"
+                .to_string(),
+        ));
+    }
+    let packets = read_recording(&options.file_name)?;
+    if packets.is_empty() {
+        warn!("recording does not contain any packets");
+        return Ok(());
+    }
+    info!("replaying {} packets", packets.len());
+
+    let start = playback_options.start.unwrap_or_default();
+    let mut pacer = FramePacer::new(&playback_options, false);
+    loop {
+        let mut frames_in_range = 0;
+        for (index, recorded) in packets.iter().enumerate() {
+            if playback_options
+                .end
+                .is_some_and(|end| recorded.timestamp >= end)
+                || playback_options
+                    .frames
+                    .is_some_and(|max| frames_in_range >= max)
+            {
+                break;
+            }
+            let in_range = recorded.timestamp >= start;
+            if in_range {
+                FramePacer::wait_until(pacer.due(recorded.timestamp - start));
+            }
+
+            let packet = Packet::try_from(recorded.bytes.as_slice())
+                .map_err(|e| Error::Decode(format!("invalid recorded packet: {e:?}")))?;
+            connection.send_packet(packet)?;
+
+            if ends_frame(recorded, packets.get(index + 1)) {
+                connection.end_frame()?;
+                if in_range {
+                    frames_in_range += 1;
+                }
+            }
+        }
+
+        if !pacer.next_iteration() {
+            break;
+        }
+    }
+    info!("finished replay");
+    Ok(())
+}
+
+/// Whether `recorded` is the last packet of a frame, which is the case if the next packet was
+/// sent after a pause.
+fn ends_frame(recorded: &RecordedPacket, next: Option<&RecordedPacket>) -> bool {
+    match next {
+        Some(next) => next.timestamp.saturating_sub(recorded.timestamp) >= FRAME_GAP,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use servicepoint::FRAME_PACING;
+
+    #[test]
+    fn frame_gap_is_shorter_than_frame_pacing() {
+        assert!(FRAME_GAP * 2 <= FRAME_PACING);
+    }
+
+    #[test]
+    fn packets_are_grouped_into_frames_by_pauses() {
+        let packets: Vec<_> = [0, 1, 3, 33, 34, 47, 80]
+            .into_iter()
+            .map(|milliseconds| RecordedPacket {
+                timestamp: Duration::from_millis(milliseconds),
+                bytes: Vec::new(),
+            })
+            .collect();
+        let frame_ends: Vec<_> = (0..packets.len())
+            .filter(|&index| ends_frame(&packets[index], packets.get(index + 1)))
+            .collect();
+        // 14 ms between the packets at 33 and 47 ms are still the same frame
+        assert_eq!(frame_ends, [2, 5, 6]);
+    }
+}
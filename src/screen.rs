@@ -0,0 +1,1007 @@
+use crate::{
+    ansi::{Action, Parser},
+    charset::text_command,
+    cli::{Charset, WrapMode},
+    error::Result,
+    transport::Transport,
+};
+use log::trace;
+use servicepoint::{
+    BinaryOperation, BitVecCommand, Brightness, BrightnessGrid, BrightnessGridCommand, CharGrid,
+    CompressionCode, DisplayBitVec, Origin, PIXEL_WIDTH, TILE_SIZE, TypedCommand,
+};
+
+/// Tab stops are every this many columns.
+const TAB_WIDTH: usize = 8;
+/// Brightness of tiles with normal text, once bold or faint text was shown.
+const NORMAL_BRIGHTNESS: u8 = 8;
+const FAINT_BRIGHTNESS: u8 = 4;
+/// Blank cells between the end and the start of a line in a marquee.
+const MARQUEE_GAP: usize = 4;
+/// Cells of a line kept for a marquee beyond its row, so endless lines cannot fill the memory.
+const MAX_OVERFLOW: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Intensity {
+    #[default]
+    Normal,
+    Bold,
+    Faint,
+}
+
+/// The text attributes the display can show. Colors are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Attributes {
+    pub intensity: Intensity,
+    pub inverse: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub character: char,
+    pub attributes: Attributes,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            character: ' ',
+            attributes: Attributes::default(),
+        }
+    }
+}
+
+/// How a [Screen] handles text, which differs between piped text and programs running in a
+/// terminal.
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenMode {
+    /// Whether a line feed also returns to the first column, as piped text has no carriage
+    /// returns.
+    pub newline_returns: bool,
+    pub wrap: WrapMode,
+    /// Whether truncated lines scroll horizontally, see [Screen::advance_marquee].
+    pub marquee: bool,
+}
+
+/// The part of a line that did not fit into its row.
+#[derive(Debug, Clone, Default)]
+struct Overflow {
+    cells: Vec<Cell>,
+    /// How far the line has scrolled in a marquee.
+    offset: usize,
+}
+
+/// The screen of a terminal, one cell per tile of the display, updated by the output of programs
+/// including their escape sequences.
+///
+/// Rows are marked as changed, so only those have to be sent to the display.
+#[derive(Debug)]
+pub struct Screen {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    changed: Vec<bool>,
+    overflow: Vec<Overflow>,
+    x: usize,
+    y: usize,
+    /// Set after writing to the last column, the next character starts a new line.
+    wrap_pending: bool,
+    /// Cleared by programs that turn off autowrap, the last column is then overwritten instead.
+    autowrap: bool,
+    attributes: Attributes,
+    saved_cursor: (usize, usize, Attributes),
+    /// Top and bottom row of the region that scrolls.
+    scroll_top: usize,
+    scroll_bottom: usize,
+    mode: ScreenMode,
+    /// The main screen while programs like editors use the alternate screen.
+    main_screen: Option<(Vec<Cell>, Vec<Overflow>)>,
+    last_character: Option<char>,
+    parser: Parser,
+    /// The start of a multi-byte character at the end of the last write.
+    incomplete: Vec<u8>,
+    /// Answers to queries of the program, like the cursor position.
+    responses: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(width: usize, height: usize, mode: ScreenMode) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::default(); width * height],
+            changed: vec![true; height],
+            overflow: vec![Overflow::default(); height],
+            x: 0,
+            y: 0,
+            wrap_pending: false,
+            autowrap: true,
+            attributes: Attributes::default(),
+            saved_cursor: (0, 0, Attributes::default()),
+            scroll_top: 0,
+            scroll_bottom: height - 1,
+            mode,
+            main_screen: None,
+            last_character: None,
+            parser: Parser::default(),
+            incomplete: Vec::new(),
+            responses: Vec::new(),
+        }
+    }
+
+    /// Characters may be split between writes. Invalid UTF-8 is shown as '?'.
+    pub fn write(&mut self, bytes: &[u8]) {
+        let mut incomplete = std::mem::take(&mut self.incomplete);
+        incomplete.extend_from_slice(bytes);
+        let mut rest = incomplete.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    self.write_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    self.write_str(std::str::from_utf8(valid).expect("checked to be valid"));
+                    let Some(length) = e.error_len() else {
+                        rest = invalid;
+                        break;
+                    };
+                    self.print(char::REPLACEMENT_CHARACTER);
+                    rest = &invalid[length..];
+                }
+            }
+        }
+        self.incomplete = rest.to_vec();
+    }
+
+    fn write_str(&mut self, text: &str) {
+        for character in text.chars() {
+            if let Some(action) = self.parser.advance(character) {
+                self.apply(action);
+            }
+        }
+    }
+
+    /// Returns what has to be sent back to the program since the last call.
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    /// The cells currently shown in a row, which are scrolled if the row is a marquee.
+    pub fn row(&self, y: usize) -> Vec<Cell> {
+        let cells = &self.cells[y * self.width..(y + 1) * self.width];
+        let overflow = &self.overflow[y];
+        if !self.mode.marquee || overflow.cells.is_empty() {
+            return cells.to_vec();
+        }
+
+        let line_length = cells.len() + overflow.cells.len() + MARQUEE_GAP;
+        cells
+            .iter()
+            .chain(&overflow.cells)
+            .copied()
+            .chain(std::iter::repeat(Cell::default()).take(MARQUEE_GAP))
+            .cycle()
+            .skip(overflow.offset % line_length)
+            .take(self.width)
+            .collect()
+    }
+
+    /// Whether any line was too long for its row.
+    pub fn is_truncated(&self) -> bool {
+        self.overflow
+            .iter()
+            .any(|overflow| !overflow.cells.is_empty())
+    }
+
+    /// Scrolls all truncated lines by one character.
+    pub fn advance_marquee(&mut self) {
+        for (y, overflow) in self.overflow.iter_mut().enumerate() {
+            if !overflow.cells.is_empty() {
+                overflow.offset += 1;
+                self.changed[y] = true;
+            }
+        }
+    }
+
+    /// Returns the rows changed since the last call.
+    pub fn take_changed_rows(&mut self) -> Vec<usize> {
+        let rows = (0..self.height).filter(|&y| self.changed[y]).collect();
+        self.changed.fill(false);
+        rows
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Print(character) => self.print(character),
+            Action::Control(character) => self.control(character),
+            Action::Csi {
+                private: None,
+                params,
+                intermediate: None,
+                final_char,
+            } => self.csi(&params, final_char),
+            Action::Csi {
+                private: Some('?'),
+                params,
+                intermediate: None,
+                final_char: final_char @ ('h' | 'l'),
+            } => {
+                for mode in params {
+                    self.set_private_mode(mode, final_char == 'h');
+                }
+            }
+            Action::Escape {
+                intermediate: None,
+                final_char,
+            } => self.escape(final_char),
+            action => trace!("ignoring unsupported terminal sequence {action:?}"),
+        }
+    }
+
+    fn print(&mut self, character: char) {
+        let cell = Cell {
+            character,
+            attributes: self.attributes,
+        };
+        if self.wrap_pending && self.autowrap {
+            match self.mode.wrap {
+                // only a marquee shows the rest of the line
+                WrapMode::Truncate => {
+                    let overflow = &mut self.overflow[self.y].cells;
+                    if self.mode.marquee && overflow.len() < MAX_OVERFLOW {
+                        overflow.push(cell);
+                    }
+                    return;
+                }
+                WrapMode::Char => {
+                    self.x = 0;
+                    self.line_feed();
+                }
+                // the space between two words is not needed at the start of the next row
+                WrapMode::Word if character == ' ' => {
+                    self.x = 0;
+                    self.line_feed();
+                    return;
+                }
+                WrapMode::Word => {
+                    let word = self.take_last_word();
+                    self.x = 0;
+                    self.line_feed();
+                    for cell in word {
+                        self.set(self.x, self.y, cell);
+                        self.x += 1;
+                    }
+                }
+            }
+        } else if self.x == 0 {
+            // the line is written again, e.g. after a carriage return
+            self.clear_overflow(self.y);
+        }
+
+        self.set(self.x, self.y, cell);
+        self.last_character = Some(character);
+        if self.x + 1 < self.width {
+            self.x += 1;
+        } else {
+            self.wrap_pending = self.autowrap;
+        }
+    }
+
+    /// Removes the word at the end of the cursor row, so it can be moved to the next row. Words
+    /// filling the whole row are left to be broken up.
+    fn take_last_word(&mut self) -> Vec<Cell> {
+        let start = self.y * self.width;
+        let row = &self.cells[start..start + self.width];
+        let Some(space) = row.iter().rposition(|cell| cell.character == ' ') else {
+            return Vec::new();
+        };
+        let word = row[space + 1..].to_vec();
+        self.erase(space + 1, self.y, self.width, self.y + 1);
+        word
+    }
+
+    fn control(&mut self, character: char) {
+        match character {
+            '\n' | '\x0b' | '\x0c' => {
+                if self.mode.newline_returns {
+                    self.x = 0;
+                }
+                self.line_feed();
+            }
+            '\r' => self.move_to(0, self.y),
+            '\x08' => self.move_to(self.x.saturating_sub(1), self.y),
+            '\t' => self.move_to((self.x / TAB_WIDTH + 1) * TAB_WIDTH, self.y),
+            _ => {}
+        }
+    }
+
+    fn csi(&mut self, params: &[u16], final_char: char) {
+        // 0 and missing parameters mean the default, which is 1 for counts and positions
+        let count = |index: usize| params.get(index).map_or(1, |&n| n.max(1) as usize);
+        let mode = params.first().copied().unwrap_or(0);
+        match final_char {
+            'A' => self.move_to(self.x, self.y.saturating_sub(count(0))),
+            'B' | 'e' => self.move_to(self.x, self.y + count(0)),
+            'C' | 'a' => self.move_to(self.x + count(0), self.y),
+            'D' => self.move_to(self.x.saturating_sub(count(0)), self.y),
+            'E' => self.move_to(0, self.y + count(0)),
+            'F' => self.move_to(0, self.y.saturating_sub(count(0))),
+            'G' | '`' => self.move_to(count(0) - 1, self.y),
+            'd' => self.move_to(self.x, count(0) - 1),
+            'H' | 'f' => self.move_to(count(1) - 1, count(0) - 1),
+            'I' => self.move_to((self.x / TAB_WIDTH + count(0)) * TAB_WIDTH, self.y),
+            'Z' => {
+                let tabs = self.x.div_ceil(TAB_WIDTH).saturating_sub(count(0));
+                self.move_to(tabs * TAB_WIDTH, self.y);
+            }
+            'J' => match mode {
+                0 => {
+                    self.erase(self.x, self.y, self.width, self.y + 1);
+                    self.erase(0, self.y + 1, self.width, self.height);
+                }
+                1 => {
+                    self.erase(0, 0, self.width, self.y);
+                    self.erase(0, self.y, self.x + 1, self.y + 1);
+                }
+                2 => self.erase(0, 0, self.width, self.height),
+                // 3 only clears the scrollback, which there is none of
+                _ => {}
+            },
+            'K' => match mode {
+                0 => self.erase(self.x, self.y, self.width, self.y + 1),
+                1 => self.erase(0, self.y, self.x + 1, self.y + 1),
+                2 => self.erase(0, self.y, self.width, self.y + 1),
+                _ => {}
+            },
+            'X' => self.erase(self.x, self.y, self.x + count(0), self.y + 1),
+            '@' => self.insert_cells(count(0)),
+            'P' => self.delete_cells(count(0)),
+            'L' if (self.scroll_top..=self.scroll_bottom).contains(&self.y) => {
+                self.scroll_down(self.y, self.scroll_bottom, count(0));
+            }
+            'M' if (self.scroll_top..=self.scroll_bottom).contains(&self.y) => {
+                self.scroll_up(self.y, self.scroll_bottom, count(0));
+            }
+            'S' => self.scroll_up(self.scroll_top, self.scroll_bottom, count(0)),
+            'T' => self.scroll_down(self.scroll_top, self.scroll_bottom, count(0)),
+            'r' => {
+                let top = count(0) - 1;
+                let bottom = params
+                    .get(1)
+                    .filter(|&&n| n > 0)
+                    .map_or(self.height, |&n| (n as usize).min(self.height))
+                    - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            'b' => {
+                if let Some(character) = self.last_character {
+                    for _ in 0..count(0).min(self.width * self.height) {
+                        self.print(character);
+                    }
+                }
+            }
+            'm' => self.select_graphic_rendition(params),
+            'n' if mode == 5 => self.responses.extend_from_slice(b"\x1b[0n"),
+            'n' if mode == 6 => {
+                let report = format!("\x1b[{};{}R", self.y + 1, self.x + 1);
+                self.responses.extend_from_slice(report.as_bytes());
+            }
+            // identifies as a VT100 with advanced video option
+            'c' if mode == 0 => self.responses.extend_from_slice(b"\x1b[?1;2c"),
+            's' => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            _ => trace!("ignoring unsupported control sequence {params:?} {final_char}"),
+        }
+    }
+
+    fn escape(&mut self, final_char: char) {
+        match final_char {
+            '7' => self.save_cursor(),
+            '8' => self.restore_cursor(),
+            'D' => self.line_feed(),
+            'E' => {
+                self.x = 0;
+                self.line_feed();
+            }
+            'M' => {
+                if self.y == self.scroll_top {
+                    self.scroll_down(self.scroll_top, self.scroll_bottom, 1);
+                } else {
+                    self.move_to(self.x, self.y.saturating_sub(1));
+                }
+            }
+            'c' => {
+                *self = Self::new(self.width, self.height, self.mode);
+            }
+            _ => trace!("ignoring unsupported escape sequence {final_char}"),
+        }
+    }
+
+    fn set_private_mode(&mut self, mode: u16, enabled: bool) {
+        match mode {
+            7 => self.autowrap = enabled,
+            47 | 1047 | 1049 => {
+                if mode == 1049 && enabled {
+                    self.save_cursor();
+                }
+                self.switch_screen(enabled);
+                if mode == 1049 && !enabled {
+                    self.restore_cursor();
+                }
+            }
+            _ => trace!("ignoring unsupported private mode {mode}"),
+        }
+    }
+
+    fn switch_screen(&mut self, alternate: bool) {
+        let size = self.cells.len();
+        match (alternate, self.main_screen.take()) {
+            (true, None) => {
+                let cells = std::mem::replace(&mut self.cells, vec![Cell::default(); size]);
+                let overflow =
+                    std::mem::replace(&mut self.overflow, vec![Overflow::default(); self.height]);
+                self.main_screen = Some((cells, overflow));
+            }
+            (false, Some((cells, overflow))) => {
+                self.cells = cells;
+                self.overflow = overflow;
+            }
+            (_, main_screen) => {
+                self.main_screen = main_screen;
+                return;
+            }
+        }
+        self.changed.fill(true);
+    }
+
+    fn select_graphic_rendition(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            self.attributes = Attributes::default();
+        }
+        let mut params = params.iter();
+        while let Some(param) = params.next() {
+            match param {
+                0 => self.attributes = Attributes::default(),
+                1 => self.attributes.intensity = Intensity::Bold,
+                2 => self.attributes.intensity = Intensity::Faint,
+                7 => self.attributes.inverse = true,
+                22 => self.attributes.intensity = Intensity::Normal,
+                27 => self.attributes.inverse = false,
+                // extended colors have more parameters, which must not be read as attributes
+                38 | 48 | 58 => match params.next() {
+                    Some(5) => {
+                        params.next();
+                    }
+                    Some(2) => {
+                        params.nth(2);
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn line_feed(&mut self) {
+        self.wrap_pending = false;
+        if self.y == self.scroll_bottom {
+            self.scroll_up(self.scroll_top, self.scroll_bottom, 1);
+        } else if self.y + 1 < self.height {
+            self.y += 1;
+        }
+    }
+
+    /// Moves the rows from `top` to `bottom` up, clearing the rows at the bottom.
+    fn scroll_up(&mut self, top: usize, bottom: usize, lines: usize) {
+        let region = &mut self.cells[top * self.width..(bottom + 1) * self.width];
+        let cells = (lines * self.width).min(region.len());
+        region.rotate_left(cells);
+        let length = region.len();
+        region[length - cells..].fill(Cell::default());
+        let overflow = &mut self.overflow[top..=bottom];
+        let rows = lines.min(overflow.len());
+        overflow.rotate_left(rows);
+        let length = overflow.len();
+        overflow[length - rows..].fill(Overflow::default());
+        self.changed[top..=bottom].fill(true);
+    }
+
+    /// Moves the rows from `top` to `bottom` down, clearing the rows at the top.
+    fn scroll_down(&mut self, top: usize, bottom: usize, lines: usize) {
+        let region = &mut self.cells[top * self.width..(bottom + 1) * self.width];
+        let cells = (lines * self.width).min(region.len());
+        region.rotate_right(cells);
+        region[..cells].fill(Cell::default());
+        let overflow = &mut self.overflow[top..=bottom];
+        let rows = lines.min(overflow.len());
+        overflow.rotate_right(rows);
+        overflow[..rows].fill(Overflow::default());
+        self.changed[top..=bottom].fill(true);
+    }
+
+    /// Inserts blank cells at the cursor, moving the rest of the line to the right.
+    fn insert_cells(&mut self, count: usize) {
+        let line = &mut self.cells[self.y * self.width + self.x..(self.y + 1) * self.width];
+        let count = count.min(line.len());
+        line.rotate_right(count);
+        line[..count].fill(Cell::default());
+        self.clear_overflow(self.y);
+        self.changed[self.y] = true;
+    }
+
+    /// Deletes the cells at the cursor, moving the rest of the line to the left.
+    fn delete_cells(&mut self, count: usize) {
+        let line = &mut self.cells[self.y * self.width + self.x..(self.y + 1) * self.width];
+        let count = count.min(line.len());
+        line.rotate_left(count);
+        let length = line.len();
+        line[length - count..].fill(Cell::default());
+        self.clear_overflow(self.y);
+        self.changed[self.y] = true;
+    }
+
+    fn erase(&mut self, x_start: usize, y_start: usize, x_end: usize, y_end: usize) {
+        for y in y_start..y_end.min(self.height) {
+            if x_end >= self.width {
+                self.clear_overflow(y);
+            }
+            for x in x_start..x_end.min(self.width) {
+                self.set(x, y, Cell::default());
+            }
+        }
+    }
+
+    fn clear_overflow(&mut self, y: usize) {
+        let overflow = &mut self.overflow[y];
+        if !overflow.cells.is_empty() {
+            *overflow = Overflow::default();
+            self.changed[y] = true;
+        }
+    }
+
+    fn move_to(&mut self, x: usize, y: usize) {
+        self.x = x.min(self.width - 1);
+        self.y = y.min(self.height - 1);
+        self.wrap_pending = false;
+    }
+
+    fn set(&mut self, x: usize, y: usize, cell: Cell) {
+        let index = y * self.width + x;
+        if self.cells[index] != cell {
+            self.cells[index] = cell;
+            self.changed[y] = true;
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = (self.x, self.y, self.attributes);
+    }
+
+    fn restore_cursor(&mut self) {
+        let (x, y, attributes) = self.saved_cursor;
+        self.move_to(x, y);
+        self.attributes = attributes;
+    }
+}
+
+/// Sends the changed rows of a [Screen] to the display.
+#[derive(Debug)]
+pub struct ScreenSender<'t> {
+    connection: &'t Transport,
+    encoder: ScreenEncoder,
+}
+
+impl<'t> ScreenSender<'t> {
+    pub fn new(connection: &'t Transport, charset: Charset) -> Self {
+        Self {
+            connection,
+            encoder: ScreenEncoder::new(charset),
+        }
+    }
+
+    pub fn send(&mut self, screen: &mut Screen) -> Result<()> {
+        for command in self.encoder.encode(screen) {
+            self.connection.send_command(command)?;
+        }
+        self.connection.end_frame()
+    }
+}
+
+/// Turns the changed rows of a [Screen] into commands.
+///
+/// Inverse cells are inverted by XOR-ing their pixels after the text was drawn. Bold and faint
+/// text is shown by the brightness of its tiles, which is only sent once the screen contained
+/// such text, so the brightness of plain text stays untouched.
+#[derive(Debug)]
+struct ScreenEncoder {
+    charset: Charset,
+    brightness: Option<BrightnessGrid>,
+}
+
+impl ScreenEncoder {
+    fn new(charset: Charset) -> Self {
+        Self {
+            charset,
+            brightness: None,
+        }
+    }
+
+    fn encode(&mut self, screen: &mut Screen) -> Vec<TypedCommand> {
+        let rows = screen.take_changed_rows();
+        if rows.is_empty() {
+            return Vec::new();
+        }
+        let width = screen.width;
+        let cells: Vec<Cell> = (0..screen.height).flat_map(|y| screen.row(y)).collect();
+        let characters = |cells: &[Cell]| {
+            let characters = cells.iter().map(|cell| cell.character).collect();
+            CharGrid::from_vec(width, characters).expect("screen has invalid size")
+        };
+
+        let mut commands = Vec::new();
+        // one large command is cheaper than many small ones, e.g. after scrolling
+        let redrawn_rows = if rows.len() > screen.height / 2 {
+            commands.push(text_command(
+                &characters(&cells),
+                Origin::ZERO,
+                self.charset,
+            ));
+            // the text of unchanged rows was drawn again, which removed their inversion
+            (0..screen.height).collect()
+        } else {
+            for &y in &rows {
+                commands.push(text_command(
+                    &characters(&cells[y * width..(y + 1) * width]),
+                    Origin::new(0, y),
+                    self.charset,
+                ));
+            }
+            rows
+        };
+
+        commands.extend(
+            redrawn_rows
+                .into_iter()
+                .filter_map(|y| inverse_command(&cells[y * width..(y + 1) * width], y)),
+        );
+        commands.extend(self.brightness_command(width, &cells));
+        commands
+    }
+
+    fn brightness_command(&mut self, width: usize, cells: &[Cell]) -> Option<TypedCommand> {
+        let has_attributes = cells
+            .iter()
+            .any(|cell| cell.attributes.intensity != Intensity::Normal);
+        if self.brightness.is_none() && !has_attributes {
+            return None;
+        }
+
+        let grid = BrightnessGrid::from_vec(
+            width,
+            cells
+                .iter()
+                .map(|cell| {
+                    Brightness::saturating_from(match cell.attributes.intensity {
+                        Intensity::Normal => NORMAL_BRIGHTNESS,
+                        Intensity::Bold => u8::from(Brightness::MAX),
+                        Intensity::Faint => FAINT_BRIGHTNESS,
+                    })
+                })
+                .collect(),
+        )
+        .expect("screen has invalid size");
+        if self.brightness.as_ref() == Some(&grid) {
+            return None;
+        }
+        self.brightness = Some(grid.clone());
+        Some(
+            BrightnessGridCommand {
+                origin: Origin::ZERO,
+                grid,
+            }
+            .into(),
+        )
+    }
+}
+
+fn inverse_command(row: &[Cell], y: usize) -> Option<TypedCommand> {
+    if !row.iter().any(|cell| cell.attributes.inverse) {
+        return None;
+    }
+
+    let mut mask = DisplayBitVec::repeat(false, TILE_SIZE * PIXEL_WIDTH);
+    for (x, cell) in row.iter().enumerate() {
+        if cell.attributes.inverse {
+            for pixel_row in 0..TILE_SIZE {
+                let start = pixel_row * PIXEL_WIDTH + x * TILE_SIZE;
+                mask[start..start + TILE_SIZE].fill(true);
+            }
+        }
+    }
+    Some(
+        BitVecCommand {
+            offset: y * TILE_SIZE * PIXEL_WIDTH,
+            bitvec: mask,
+            compression: CompressionCode::default(),
+            operation: BinaryOperation::Xor,
+        }
+        .into(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use servicepoint::Grid;
+
+    fn screen(width: usize, height: usize, newline_returns: bool) -> Screen {
+        Screen::new(
+            width,
+            height,
+            ScreenMode {
+                newline_returns,
+                wrap: WrapMode::Truncate,
+                marquee: false,
+            },
+        )
+    }
+
+    fn terminal(text: &str) -> Screen {
+        let mut screen = screen(10, 4, false);
+        screen.write(text.as_bytes());
+        screen
+    }
+
+    fn rows(screen: &Screen) -> Vec<String> {
+        (0..screen.height)
+            .map(|y| {
+                let row: String = screen.row(y).iter().map(|cell| cell.character).collect();
+                row.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    fn attributes(screen: &Screen, x: usize, y: usize) -> Attributes {
+        screen.row(y)[x].attributes
+    }
+
+    #[test]
+    fn graphic_rendition() {
+        let screen = terminal("\x1b[1mB\x1b[2mF\x1b[22;7mI\x1b[27mN\x1b[1;7mX\x1b[mP");
+        let expected = [
+            (Intensity::Bold, false),
+            (Intensity::Faint, false),
+            (Intensity::Normal, true),
+            (Intensity::Normal, false),
+            (Intensity::Bold, true),
+            (Intensity::Normal, false),
+        ];
+        for (x, (intensity, inverse)) in expected.into_iter().enumerate() {
+            assert_eq!(
+                attributes(&screen, x, 0),
+                Attributes { intensity, inverse },
+                "cell {x}"
+            );
+        }
+    }
+
+    #[test]
+    fn graphic_rendition_skips_color_parameters() {
+        let screen = terminal("\x1b[38;5;1mA\x1b[48;2;7;1;2mB");
+        assert_eq!(attributes(&screen, 0, 0), Attributes::default());
+        assert_eq!(attributes(&screen, 1, 0), Attributes::default());
+    }
+
+    #[test]
+    fn cursor_movement() {
+        let screen = terminal("\x1b[2;3Ha\x1b[Ab\x1b[2Bc\x1b[5Dd\x1b[99;99He\x1b[1;1Hf");
+        assert_eq!(rows(&screen), ["f  b", "  a", "d   c", "         e"]);
+    }
+
+    #[test]
+    fn line_feed_without_carriage_return_keeps_column() {
+        let screen = terminal("ab\ncd\r\nef");
+        assert_eq!(rows(&screen), ["ab", "  cd", "ef", ""]);
+    }
+
+    #[test]
+    fn line_feed_at_bottom_scrolls() {
+        let screen = terminal("1\r\n2\r\n3\r\n4\r\n5");
+        assert_eq!(rows(&screen), ["2", "3", "4", "5"]);
+    }
+
+    #[test]
+    fn erase_in_line() {
+        let filled = "0123456789\x1b[1;5H";
+        assert_eq!(rows(&terminal(&format!("{filled}\x1b[K")))[0], "0123");
+        assert_eq!(
+            rows(&terminal(&format!("{filled}\x1b[1K")))[0],
+            "     56789"
+        );
+        assert_eq!(rows(&terminal(&format!("{filled}\x1b[2K")))[0], "");
+        assert_eq!(
+            rows(&terminal(&format!("{filled}\x1b[3X")))[0],
+            "0123   789"
+        );
+    }
+
+    #[test]
+    fn erase_in_display() {
+        let filled = "aaa\r\nbbb\r\nccc\x1b[2;2H";
+        assert_eq!(
+            rows(&terminal(&format!("{filled}\x1b[J"))),
+            ["aaa", "b", "", ""]
+        );
+        assert_eq!(
+            rows(&terminal(&format!("{filled}\x1b[1J"))),
+            ["", "  b", "ccc", ""]
+        );
+        assert_eq!(
+            rows(&terminal(&format!("{filled}\x1b[2J"))),
+            ["", "", "", ""]
+        );
+        // only clears the scrollback
+        assert_eq!(
+            rows(&terminal(&format!("{filled}\x1b[3J"))),
+            ["aaa", "bbb", "ccc", ""]
+        );
+    }
+
+    #[test]
+    fn cursor_position_report() {
+        let mut screen = terminal("\x1b[3;4H\x1b[6n");
+        assert_eq!(screen.take_responses(), b"\x1b[3;4R");
+        assert!(screen.take_responses().is_empty());
+    }
+
+    #[test]
+    fn alternate_screen_restores_main_screen() {
+        let screen = terminal("main\x1b[?1049h\x1b[2Jalternate\x1b[?1049lx");
+        assert_eq!(rows(&screen), ["mainx", "", "", ""]);
+    }
+
+    #[test]
+    fn characters_split_between_writes() {
+        let mut screen = screen(10, 4, true);
+        let bytes = "äö".as_bytes();
+        screen.write(&bytes[..1]);
+        screen.write(&bytes[1..3]);
+        screen.write(&bytes[3..]);
+        screen.write(b"\xffx");
+        assert_eq!(rows(&screen)[0], "äö\u{fffd}x");
+    }
+
+    fn wrapped(wrap: WrapMode, marquee: bool, text: &str) -> Screen {
+        let mut screen = Screen::new(
+            10,
+            4,
+            ScreenMode {
+                newline_returns: true,
+                wrap,
+                marquee,
+            },
+        );
+        screen.write(text.as_bytes());
+        screen
+    }
+
+    #[test]
+    fn character_wrap() {
+        let screen = wrapped(WrapMode::Char, false, "0123456789abc");
+        assert_eq!(rows(&screen), ["0123456789", "abc", "", ""]);
+        assert!(!screen.is_truncated());
+    }
+
+    #[test]
+    fn word_wrap_moves_the_last_word() {
+        let screen = wrapped(WrapMode::Word, false, "hello world");
+        assert_eq!(rows(&screen), ["hello", "world", "", ""]);
+    }
+
+    #[test]
+    fn word_wrap_at_the_row_end_drops_the_space() {
+        let screen = wrapped(WrapMode::Word, false, "hello abcd next");
+        assert_eq!(rows(&screen), ["hello abcd", "next", "", ""]);
+    }
+
+    #[test]
+    fn word_wrap_breaks_words_longer_than_a_row() {
+        let screen = wrapped(WrapMode::Word, false, "abcdefghijklm no");
+        assert_eq!(rows(&screen), ["abcdefghij", "klm no", "", ""]);
+    }
+
+    #[test]
+    fn truncated_lines_are_only_kept_for_a_marquee() {
+        let screen = wrapped(WrapMode::Truncate, false, "0123456789abc");
+        assert_eq!(rows(&screen)[0], "0123456789");
+        assert!(!screen.is_truncated());
+    }
+
+    #[test]
+    fn truncated_lines_are_kept_until_rewritten() {
+        let mut screen = wrapped(WrapMode::Truncate, true, "0123456789abc\nx");
+        assert_eq!(rows(&screen), ["0123456789", "x", "", ""]);
+        assert!(screen.is_truncated());
+
+        screen.write(b"\x1b[1;1Hshort");
+        assert_eq!(rows(&screen)[0], "short56789");
+        assert!(!screen.is_truncated());
+    }
+
+    #[test]
+    fn marquee_scrolls_truncated_lines() {
+        let mut screen = wrapped(WrapMode::Truncate, true, "0123456789ab\nshort");
+        screen.take_changed_rows();
+        for _ in 0..3 {
+            screen.advance_marquee();
+        }
+        assert_eq!(screen.take_changed_rows(), [0]);
+        assert_eq!(rows(&screen), ["3456789ab", "short", "", ""]);
+
+        // the line length including the gap is 16
+        for _ in 0..13 {
+            screen.advance_marquee();
+        }
+        assert_eq!(rows(&screen)[0], "0123456789");
+    }
+
+    #[test]
+    fn marquee_keeps_a_bounded_part_of_long_lines() {
+        let line = "x".repeat(10 + 2 * MAX_OVERFLOW);
+        let screen = wrapped(WrapMode::Truncate, true, &line);
+        assert_eq!(screen.overflow[0].cells.len(), MAX_OVERFLOW);
+    }
+
+    #[test]
+    fn disabled_autowrap_overwrites_the_last_column() {
+        let mut screen = wrapped(WrapMode::Char, false, "\x1b[?7l");
+        for percent in 0..1000 {
+            screen.write(format!("\rprogress {percent}%").as_bytes());
+        }
+        assert_eq!(rows(&screen), ["progress %", "", "", ""]);
+        assert!(
+            screen
+                .overflow
+                .iter()
+                .all(|overflow| overflow.cells.is_empty())
+        );
+
+        screen.write(b"\x1b[?7hab");
+        assert_eq!(rows(&screen), ["progress a", "b", "", ""]);
+    }
+
+    #[test]
+    fn full_redraw_inverts_unchanged_rows_again() {
+        let mut screen = screen(56, 20, true);
+        let mut encoder = ScreenEncoder::new(Charset::Cp437);
+        screen.write(b"\x1b[7mTOP\x1b[0m");
+        encoder.encode(&mut screen);
+
+        screen.write(b"\x1b[3;1H");
+        screen.write("line\n".repeat(15).as_bytes());
+        let commands = encoder.encode(&mut screen);
+
+        assert_eq!(commands.len(), 2, "{commands:?}");
+        let TypedCommand::Cp437Grid(text) = &commands[0] else {
+            panic!("expected the whole grid, got {:?}", commands[0]);
+        };
+        assert_eq!((text.grid.width(), text.grid.height()), (56, 20));
+        let TypedCommand::BitVec(inverse) = &commands[1] else {
+            panic!(
+                "expected the inversion of the first row, got {:?}",
+                commands[1]
+            );
+        };
+        assert_eq!(inverse.offset, 0);
+        assert_eq!(inverse.operation, BinaryOperation::Xor);
+        assert!(inverse.bitvec[..3 * TILE_SIZE].all());
+        assert!(inverse.bitvec[3 * TILE_SIZE..PIXEL_WIDTH].not_any());
+    }
+}
@@ -0,0 +1,98 @@
+use log::info;
+use servicepoint::{CommandCode, Header, Packet};
+use std::time::{Duration, Instant};
+
+/// How often the statistics are logged while sending.
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Counts what was sent, to be logged periodically and once more when done.
+#[derive(Debug)]
+pub struct Statistics {
+    start: Instant,
+    last_report: Instant,
+    packets: usize,
+    bytes: usize,
+    uncompressed_bytes: usize,
+    skipped_frames: usize,
+    throttled: Duration,
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            last_report: now,
+            packets: 0,
+            bytes: 0,
+            uncompressed_bytes: 0,
+            skipped_frames: 0,
+            throttled: Duration::ZERO,
+        }
+    }
+}
+
+impl Statistics {
+    /// Counts a packet once for every target it was sent to.
+    pub fn packet_sent(&mut self, packet: &Packet, targets: usize) {
+        self.packets += targets;
+        self.bytes += packet.size() * targets;
+        self.uncompressed_bytes += Self::uncompressed_size(packet) * targets;
+        if self.last_report.elapsed() >= REPORT_INTERVAL {
+            self.report("");
+            self.last_report = Instant::now();
+        }
+    }
+
+    pub fn frame_skipped(&mut self) {
+        self.skipped_frames += 1;
+    }
+
+    pub fn throttled(&mut self, duration: Duration) {
+        self.throttled += duration;
+    }
+
+    /// The size of the packet if its payload was not compressed.
+    fn uncompressed_size(packet: &Packet) -> usize {
+        let Header { b, c, d, .. } = packet.header;
+        let payload = match CommandCode::try_from(packet.header.command_code) {
+            Ok(
+                CommandCode::BitmapLinear
+                | CommandCode::BitmapLinearAnd
+                | CommandCode::BitmapLinearOr
+                | CommandCode::BitmapLinearXor,
+            ) => b as usize,
+            Ok(
+                CommandCode::BitmapLinearWinUncompressed
+                | CommandCode::BitmapLinearWinZlib
+                | CommandCode::BitmapLinearWinBzip2
+                | CommandCode::BitmapLinearWinLzma
+                | CommandCode::BitmapLinearWinZstd,
+            ) => c as usize * d as usize,
+            _ => packet.payload.as_ref().map_or(0, Vec::len),
+        };
+        size_of::<Header>() + payload
+    }
+
+    fn report(&self, prefix: &str) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let compression_ratio = self.bytes as f64 / self.uncompressed_bytes.max(1) as f64;
+        info!(
+            "{prefix}sent {} packets ({:.1} per second), {} bytes ({:.0} per second), \
+            compressed to {:.0}%, {} frames skipped, {:.1?} waited for rate limit",
+            self.packets,
+            self.packets as f64 / elapsed,
+            self.bytes,
+            self.bytes as f64 / elapsed,
+            compression_ratio * 100.0,
+            self.skipped_frames,
+            self.throttled
+        );
+    }
+}
+
+impl Drop for Statistics {
+    fn drop(&mut self) {
+        self.report("in total: ");
+    }
+}
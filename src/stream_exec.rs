@@ -0,0 +1,91 @@
+use crate::{
+    cli::{ExecOptions, TextOptions, WrapMode},
+    error::{Error, Result},
+    pty::{Pty, RawMode},
+    screen::{Screen, ScreenMode, ScreenSender},
+    stream_stdin::read_chunks,
+    transport::Transport,
+};
+use log::{info, warn};
+use servicepoint::*;
+use std::{
+    io::{IsTerminal, Write},
+    sync::mpsc::{RecvTimeoutError, channel},
+    thread,
+    time::Instant,
+};
+
+/// Runs a program in a pseudo terminal with the size of the display and mirrors its screen.
+pub(crate) fn stream_exec(
+    connection: &Transport,
+    options: ExecOptions,
+    text_options: &TextOptions,
+) -> Result<()> {
+    let mut pty = Pty::spawn(&options.command, TILE_WIDTH, TILE_HEIGHT, &options.term)?;
+    let program = &options.command[0];
+    info!("running {program} in a {TILE_WIDTH}x{TILE_HEIGHT} terminal");
+
+    let clone_master = |pty: &Pty| {
+        pty.master
+            .try_clone()
+            .map_err(|e| Error::io("failed to set up pseudo terminal", e))
+    };
+    let output = clone_master(&pty)?;
+    let (sender, receiver) = channel();
+    thread::spawn(move || read_chunks(output, sender));
+
+    // key presses go to the program instead of being handled by the local terminal
+    let stdin = std::io::stdin();
+    let raw_mode = if stdin.is_terminal() {
+        Some(RawMode::enable(&stdin)?)
+    } else {
+        None
+    };
+    let mut input = clone_master(&pty)?;
+    thread::spawn(move || std::io::copy(&mut std::io::stdin().lock(), &mut input));
+
+    let mode = ScreenMode {
+        newline_returns: false,
+        wrap: WrapMode::Char,
+        marquee: false,
+    };
+    let mut screen = Screen::new(TILE_WIDTH, TILE_HEIGHT, mode);
+    let mut screen_sender = ScreenSender::new(connection, text_options.charset);
+    connection.send_command(ClearCommand)?;
+
+    // programs write in many small pieces, which are collected into one update per frame
+    let mut last_send = Instant::now();
+    loop {
+        match receiver.recv_timeout(FRAME_PACING) {
+            Ok(Ok(bytes)) => {
+                screen.write(&bytes);
+                let responses = screen.take_responses();
+                if !responses.is_empty() {
+                    pty.master
+                        .write_all(&responses)
+                        .map_err(|e| Error::io("failed to answer program", e))?;
+                }
+            }
+            Ok(Err(e)) => return Err(Error::io("failed to read output of program", e)),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if last_send.elapsed() >= FRAME_PACING {
+            screen_sender.send(&mut screen)?;
+            last_send = Instant::now();
+        }
+    }
+    screen_sender.send(&mut screen)?;
+
+    let status = pty
+        .child
+        .wait()
+        .map_err(|e| Error::io("failed to wait for program", e))?;
+    drop(raw_mode);
+    if status.success() {
+        info!("{program} exited");
+    } else {
+        warn!("{program} exited with {status}");
+    }
+    Ok(())
+}
@@ -1,88 +1,147 @@
-use crate::transport::Transport;
-use log::warn;
+use crate::{
+    cli::{StdinOptions, TextOptions, WrapMode},
+    error::{Error, Result},
+    screen::{Screen, ScreenMode, ScreenSender},
+    transport::Transport,
+};
 use servicepoint::*;
-use std::thread::sleep;
+use std::{
+    io::{ErrorKind, Read},
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel},
+    thread::{self, sleep},
+    time::Duration,
+};
 
-pub(crate) fn stream_stdin(connection: &Transport, slow: bool) {
-    warn!(
-        "This mode will break when using multi-byte characters and does not support ANSI escape sequences yet."
-    );
+/// How long each step of a marquee is shown.
+const MARQUEE_INTERVAL: Duration = Duration::from_millis(200);
+const READ_BUFFER_SIZE: usize = 8192;
+
+pub(crate) fn stream_stdin(
+    connection: &Transport,
+    options: StdinOptions,
+    text_options: &TextOptions,
+) -> Result<()> {
+    let mode = ScreenMode {
+        newline_returns: true,
+        wrap: if options.marquee {
+            WrapMode::Truncate
+        } else {
+            options.wrap
+        },
+        marquee: options.marquee,
+    };
     let mut app = App {
         connection,
-        mirror: CharGrid::new(TILE_WIDTH, TILE_HEIGHT),
-        y: 0,
-        slow,
+        screen: Screen::new(TILE_WIDTH, TILE_HEIGHT, mode),
+        sender: ScreenSender::new(connection, text_options.charset),
+        pending_newline: false,
+        options,
     };
     app.run()
 }
 
 struct App<'t> {
     connection: &'t Transport,
-    mirror: CharGrid,
-    y: usize,
-    slow: bool,
+    screen: Screen,
+    sender: ScreenSender<'t>,
+    pending_newline: bool,
+    options: StdinOptions,
 }
 
 impl App<'_> {
-    fn run(&mut self) {
-        self.connection
-            .send_command(ClearCommand)
-            .expect("couldn't clear screen");
-        let last_y = self.mirror.height() - 1;
-        for line in std::io::stdin().lines() {
-            let line = line.expect("could not read from stdin");
+    fn run(&mut self) -> Result<()> {
+        self.connection.send_command(ClearCommand)?;
 
-            if self.y <= last_y {
-                self.single_line(&line);
-                self.y += 1;
+        // read in the background, so marquees keep scrolling while waiting for input
+        let (sender, receiver) = channel();
+        thread::spawn(move || read_chunks(std::io::stdin().lock(), sender));
+        while let Some(bytes) = self.receive(&receiver)? {
+            if self.options.slow {
+                // every line is shown for a moment
+                for line in bytes.split_inclusive(|&byte| byte == b'\n') {
+                    self.write(line)?;
+                    if line.ends_with(b"\n") {
+                        sleep(FRAME_PACING);
+                    }
+                }
             } else {
-                self.shift_rows();
-                Self::line_onto_grid(&mut self.mirror, last_y, &line);
-                self.send_mirror()
-                // we stay on last y
-            }
-
-            if self.slow {
-                sleep(FRAME_PACING);
+                self.write(&bytes)?;
             }
         }
-    }
 
-    fn shift_rows(&mut self) {
-        let data = self.mirror.data_ref_mut();
-        data.rotate_left(TILE_WIDTH);
-        if let Some(row) = data.last_chunk_mut::<TILE_WIDTH>() {
-            row.fill(' ')
+        // the last lines keep scrolling until the program is stopped
+        while self.options.marquee && self.screen.is_truncated() {
+            sleep(MARQUEE_INTERVAL);
+            self.screen.advance_marquee();
+            self.sender.send(&mut self.screen)?;
         }
+        Ok(())
     }
 
-    fn line_onto_grid(grid: &mut CharGrid, y: usize, line: &str) {
-        for (x, char) in line.chars().enumerate() {
-            if x < grid.width() {
-                grid.set(x, y, char);
+    /// Returns `None` at the end of the input. Marquees are scrolled while waiting.
+    fn receive(
+        &mut self,
+        receiver: &Receiver<std::io::Result<Vec<u8>>>,
+    ) -> Result<Option<Vec<u8>>> {
+        let received = if self.options.marquee {
+            loop {
+                match receiver.recv_timeout(MARQUEE_INTERVAL) {
+                    Ok(received) => break Some(received),
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.screen.advance_marquee();
+                        self.sender.send(&mut self.screen)?;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break None,
+                }
             }
-        }
+        } else {
+            receiver.recv().ok()
+        };
+        received
+            .transpose()
+            .map_err(|e| Error::io("could not read from stdin", e))
     }
 
-    fn send_mirror(&self) {
-        self.connection
-            .send_command(CharGridCommand {
-                origin: Origin::ZERO,
-                grid: self.mirror.clone(),
-            })
-            .expect("couldn't send screen to display");
+    /// Holds back a line feed at the end of the input until more input follows, so the last
+    /// line stays on the bottom row instead of being scrolled up right away.
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        if self.pending_newline {
+            self.screen.write(b"\n");
+        }
+        let bytes = match bytes.strip_suffix(b"\n") {
+            Some(bytes) => {
+                self.pending_newline = true;
+                bytes
+            }
+            None => {
+                self.pending_newline = false;
+                bytes
+            }
+        };
+        self.screen.write(bytes);
+        self.sender.send(&mut self.screen)
     }
+}
 
-    fn single_line(&mut self, line: &str) {
-        let mut line_grid = CharGrid::new(TILE_WIDTH, 1);
-        line_grid.fill(' ');
-        Self::line_onto_grid(&mut line_grid, 0, line);
-        Self::line_onto_grid(&mut self.mirror, self.y, line);
-        self.connection
-            .send_command(CharGridCommand {
-                origin: Origin::new(0, self.y),
-                grid: line_grid,
-            })
-            .expect("couldn't send single line to screen");
+/// Sends whatever can be read until the end of the input or the first error.
+pub(crate) fn read_chunks(mut reader: impl Read, sender: Sender<std::io::Result<Vec<u8>>>) {
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return,
+            Ok(size) => Ok(buffer[..size].to_vec()),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            // Linux reports a closed pseudo terminal like this instead of the end of the input
+            #[cfg(unix)]
+            Err(e) if e.raw_os_error() == Some(libc::EIO) => return,
+            Err(e) => Err(e),
+        };
+        let failed = read.is_err();
+        if sender.send(read).is_err() || failed {
+            return;
+        }
     }
 }
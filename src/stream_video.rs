@@ -0,0 +1,247 @@
+use crate::{
+    cli::{FrameDiffOptions, ImageProcessingOptions, PlaybackOptions, SendImageOptions},
+    error::{Error, Result},
+    frame_pacing::FramePacer,
+    image_processing::ImageProcessingPipeline,
+    transport::Transport,
+    wall::WallSender,
+};
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::Rescale;
+use image::{DynamicImage, RgbImage};
+use log::{debug, info, warn};
+use servicepoint::FRAME_PACING;
+use std::time::Duration;
+
+pub fn stream_video(
+    connection: &Transport,
+    options: SendImageOptions,
+    playback_options: PlaybackOptions,
+    processing_options: ImageProcessingOptions,
+    frame_diff_options: FrameDiffOptions,
+) -> Result<()> {
+    ffmpeg::init().map_err(|e| decode_error("failed to initialize ffmpeg", e))?;
+
+    let mut ictx = ffmpeg::format::input(&options.file_name)
+        .map_err(|e| decode_error("failed to open video input file", e))?;
+
+    let input = ictx
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .ok_or_else(|| Error::UnsupportedFormat("input file has no video stream".to_string()))?;
+    let video_stream_index = input.index();
+    let clock = VideoClock::new(&input);
+
+    let context_decoder = ffmpeg::codec::context::Context::from_parameters(input.parameters())
+        .map_err(|e| decode_error("could not extract video context from parameters", e))?;
+    let decoder = context_decoder.decoder().video().map_err(|e| {
+        Error::UnsupportedFormat(format!("failed to create decoder for video stream: {e}"))
+    })?;
+
+    let scaler = ffmpeg::software::scaling::Context::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        ffmpeg::format::Pixel::RGB24,
+        decoder.width(),
+        decoder.height(),
+        ffmpeg::software::scaling::Flags::BILINEAR,
+    )
+    .map_err(|e| decode_error("failed to create scaling context", e))?;
+
+    let mut player = VideoPlayer {
+        video_stream_index,
+        decoder,
+        scaler,
+        clock,
+        pipeline: ImageProcessingPipeline::new(processing_options, connection.wall_layout()),
+        sender: WallSender::new(connection, frame_diff_options),
+        pacer: FramePacer::new(&playback_options, connection.is_live()),
+        start: playback_options.start.unwrap_or_default(),
+        end: playback_options.end,
+        max_frames: playback_options.frames,
+        frames_in_range: 0,
+        total_frames: 0,
+    };
+
+    let mut needs_seek = player.start > Duration::ZERO;
+    loop {
+        if needs_seek {
+            player.seek(&mut ictx)?;
+        }
+        player.play_once(&mut ictx)?;
+
+        if player.frames_in_range == 0 {
+            warn!("no frames in the selected range, stopping playback");
+            break;
+        }
+        if !player.pacer.next_iteration() {
+            break;
+        }
+        needs_seek = true;
+    }
+
+    info!(
+        "finished video after {} frames, dropped {}",
+        player.total_frames,
+        player.pacer.dropped_frames()
+    );
+    Ok(())
+}
+
+fn decode_error(context: &str, error: ffmpeg::Error) -> Error {
+    Error::Decode(format!("{context}: {error}"))
+}
+
+struct VideoPlayer<'t> {
+    video_stream_index: usize,
+    decoder: ffmpeg::decoder::Video,
+    scaler: ffmpeg::software::scaling::Context,
+    clock: VideoClock,
+    pipeline: ImageProcessingPipeline,
+    sender: WallSender<'t>,
+    pacer: FramePacer,
+    start: Duration,
+    end: Option<Duration>,
+    max_frames: Option<usize>,
+    frames_in_range: usize,
+    total_frames: usize,
+}
+
+impl VideoPlayer<'_> {
+    /// Seeks to the last key frame before the start position. The frames between the key frame
+    /// and the start position still have to be decoded, but are not processed.
+    fn seek(&mut self, ictx: &mut ffmpeg::format::context::Input) -> Result<()> {
+        let target = self.clock.seek_target(self.start);
+        debug!("seeking to {:?} ({target})", self.start);
+        ictx.seek(target, ..target)
+            .map_err(|e| decode_error("failed to seek in video file", e))?;
+        self.decoder.flush();
+        self.clock.reset();
+        Ok(())
+    }
+
+    fn play_once(&mut self, ictx: &mut ffmpeg::format::context::Input) -> Result<()> {
+        self.frames_in_range = 0;
+
+        for (stream, packet) in ictx.packets() {
+            if stream.index() == self.video_stream_index {
+                self.decoder
+                    .send_packet(&packet)
+                    .map_err(|e| decode_error("failed to send video packet", e))?;
+                if !self.receive_and_process_decoded_frames()? {
+                    return Ok(());
+                }
+            }
+        }
+        self.decoder
+            .send_eof()
+            .map_err(|e| decode_error("failed to send eof", e))?;
+        self.receive_and_process_decoded_frames()?;
+        Ok(())
+    }
+
+    /// Returns false as soon as the end of the selected range is reached.
+    fn receive_and_process_decoded_frames(&mut self) -> Result<bool> {
+        let mut decoded = ffmpeg::util::frame::video::Video::empty();
+        let mut rgb_frame = ffmpeg::util::frame::video::Video::empty();
+        while self.decoder.receive_frame(&mut decoded).is_ok() {
+            let time = self.clock.time(decoded.timestamp());
+            if time < self.start {
+                continue;
+            }
+            if self.end.is_some_and(|end| time >= end)
+                || self
+                    .max_frames
+                    .is_some_and(|max| self.frames_in_range >= max)
+            {
+                return Ok(false);
+            }
+            self.frames_in_range += 1;
+            self.total_frames += 1;
+
+            let Some(due) = self.pacer.schedule(time - self.start) else {
+                self.sender.skip();
+                continue;
+            };
+
+            self.scaler
+                .run(&decoded, &mut rgb_frame)
+                .map_err(|e| decode_error("failed to scale frame", e))?;
+
+            let image = RgbImage::from_raw(
+                rgb_frame.width(),
+                rgb_frame.height(),
+                rgb_frame.data(0).to_owned(),
+            )
+            .ok_or_else(|| Error::Decode("could not read rgb data to image".to_string()))?;
+            let image = DynamicImage::from(image);
+            let frame = self.pipeline.process(image);
+
+            FramePacer::wait_until(due);
+            self.sender.send(frame)?;
+        }
+        Ok(true)
+    }
+}
+
+/// Converts frame timestamps of a video stream to positions relative to the start of the stream.
+#[derive(Debug)]
+struct VideoClock {
+    time_base: ffmpeg::Rational,
+    stream_start: i64,
+    frame_duration: Duration,
+    last_time: Option<Duration>,
+}
+
+impl VideoClock {
+    fn new(stream: &ffmpeg::format::stream::Stream) -> Self {
+        let time_base = stream.time_base();
+        let stream_start = match stream.start_time() {
+            ffmpeg::ffi::AV_NOPTS_VALUE => 0,
+            start => start,
+        };
+        let frame_rate = f64::from(stream.avg_frame_rate());
+        let frame_duration = if frame_rate.is_normal() && frame_rate > 0.0 {
+            Duration::from_secs_f64(1.0 / frame_rate)
+        } else {
+            FRAME_PACING
+        };
+        debug!("video time base is {time_base}, frame duration is {frame_duration:?}");
+        Self {
+            time_base,
+            stream_start,
+            frame_duration,
+            last_time: None,
+        }
+    }
+
+    /// Frames without a timestamp are placed according to the average frame rate.
+    fn time(&mut self, timestamp: Option<i64>) -> Duration {
+        let time = match (timestamp, self.last_time) {
+            (Some(timestamp), _) => {
+                let seconds =
+                    timestamp.saturating_sub(self.stream_start) as f64 * f64::from(self.time_base);
+                Duration::from_secs_f64(seconds.max(0.0))
+            }
+            (None, Some(last_time)) => last_time + self.frame_duration,
+            (None, None) => Duration::ZERO,
+        };
+        self.last_time = Some(time);
+        time
+    }
+
+    /// Calculates the timestamp to pass to [ffmpeg::format::context::Input::seek], which expects
+    /// [ffmpeg::rescale::TIME_BASE] units.
+    fn seek_target(&self, position: Duration) -> i64 {
+        let stream_start = self
+            .stream_start
+            .rescale(self.time_base, ffmpeg::rescale::TIME_BASE);
+        let position = i64::try_from(position.as_micros()).unwrap_or(i64::MAX);
+        stream_start.saturating_add(position)
+    }
+
+    fn reset(&mut self) {
+        self.last_time = None;
+    }
+}
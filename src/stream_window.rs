@@ -1,64 +1,59 @@
 use crate::{
-    cli::{ImageProcessingOptions, StreamScreenOptions},
+    cli::{FrameDiffOptions, ImageProcessingOptions, StreamScreenOptions},
+    error::{Error, Result},
     image_processing::ImageProcessingPipeline,
     transport::Transport,
+    wall::WallSender,
 };
 use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
-use log::{debug, error, info, trace, warn};
+use log::{debug, info, trace, warn};
 use scap::{
-    capturer::{Capturer, Options},
+    capturer::{Capturer, CapturerBuildError, Options},
     frame::Frame,
     frame::convert_bgra_to_rgb,
 };
-use servicepoint::{BitmapCommand, CompressionCode, FRAME_PACING, Origin};
+use servicepoint::FRAME_PACING;
 use std::time::{Duration, Instant};
 
 pub fn stream_window(
     connection: &Transport,
     options: StreamScreenOptions,
     processing_options: ImageProcessingOptions,
-) {
+    frame_diff_options: FrameDiffOptions,
+) -> Result<()> {
     info!("Starting capture with options: {:?}", options);
-    let capturer = match start_capture(&options) {
-        Some(value) => value,
-        None => return,
-    };
+    let capturer = start_capture(&options)?;
 
-    let mut pipeline = ImageProcessingPipeline::new(processing_options);
+    let mut pipeline = ImageProcessingPipeline::new(processing_options, connection.wall_layout());
+    let mut sender = WallSender::new(connection, frame_diff_options);
 
     info!("now starting to stream images");
     loop {
         let start = Instant::now();
 
-        let frame = capture_frame(&capturer);
-        let frame = frame_to_image(frame);
-        let bitmap = pipeline.process(frame);
+        let frame = capture_frame(&capturer)?;
+        let frame = frame_to_image(frame)?;
+        let frame = pipeline.process(frame);
 
         trace!("bitmap ready to send in: {:?}", start.elapsed());
 
-        connection
-            .send_command(BitmapCommand {
-                origin: Origin::ZERO,
-                bitmap: bitmap.clone(),
-                compression: CompressionCode::default(),
-            })
-            .expect("failed to send frame to display");
+        sender.send(frame)?;
 
         debug!("frame time: {:?}", start.elapsed());
     }
 }
 
-fn start_capture(options: &StreamScreenOptions) -> Option<Capturer> {
+fn start_capture(options: &StreamScreenOptions) -> Result<Capturer> {
     if !scap::is_supported() {
-        error!("platform not supported by scap");
-        return None;
+        return Err(Error::UnsupportedFormat(
+            "screen capture is not supported on this platform".to_string(),
+        ));
     }
 
     if !scap::has_permission() {
         warn!("requesting screen recording permission");
         if !scap::request_permission() {
-            error!("screen recording ermission denied");
-            return None;
+            return Err(Error::Permission("screen recording".to_string()));
         }
     }
 
@@ -69,19 +64,28 @@ fn start_capture(options: &StreamScreenOptions) -> Option<Capturer> {
         output_type: scap::frame::FrameType::BGR0,
         ..Default::default()
     })
-    .expect("failed to create screen capture");
+    .map_err(|e| match e {
+        CapturerBuildError::NotSupported => {
+            Error::UnsupportedFormat("screen capture is not supported on this platform".to_string())
+        }
+        CapturerBuildError::PermissionNotGranted => {
+            Error::Permission("screen recording".to_string())
+        }
+    })?;
     capturer.start_capture();
-    Some(capturer)
+    Ok(capturer)
 }
 
-fn capture_frame(capturer: &Capturer) -> Frame {
+fn capture_frame(capturer: &Capturer) -> Result<Frame> {
     let start_time = Instant::now();
-    let result = capturer.get_next_frame().expect("failed to capture frame");
+    let result = capturer
+        .get_next_frame()
+        .map_err(|e| Error::Decode(format!("failed to capture frame: {e}")))?;
     trace!("capture took: {:?}", start_time.elapsed());
-    result
+    Ok(result)
 }
 
-fn frame_to_image(frame: Frame) -> DynamicImage {
+fn frame_to_image(frame: Frame) -> Result<DynamicImage> {
     let start_time = Instant::now();
     let result = match frame {
         Frame::BGRx(frame) => bgrx_to_rgb(frame.width, frame.height, frame.data),
@@ -99,10 +103,14 @@ fn frame_to_image(frame: Frame) -> DynamicImage {
                 .unwrap(),
         ),
         Frame::BGRA(frame) => bgrx_to_rgb(frame.width, frame.height, frame.data),
-        Frame::YUVFrame(_) | Frame::XBGR(_) => panic!("unsupported frame format"),
+        Frame::YUVFrame(_) | Frame::XBGR(_) => {
+            return Err(Error::UnsupportedFormat(
+                "screen capture frame format".to_string(),
+            ));
+        }
     };
     trace!("conversion to image took: {:?}", start_time.elapsed());
-    result
+    Ok(result)
 }
 
 fn bgrx_to_rgb(width: i32, height: i32, data: Vec<u8>) -> DynamicImage {
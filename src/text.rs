@@ -1,7 +1,45 @@
-use crate::{cli::TextCommand, stream_stdin::stream_stdin, transport::Transport};
+use crate::{
+    cli::TextCommand, error::Result, input::read_input, stream_stdin::stream_stdin,
+    text_layout::show_text, transport::Transport,
+};
 
-pub fn text(connection: &Transport, command: TextCommand) {
+pub fn text(connection: &Transport, command: TextCommand) -> Result<()> {
     match command {
-        TextCommand::Stdin { slow } => stream_stdin(connection, slow),
+        TextCommand::Stdin {
+            stdin_options,
+            text_options,
+        } => stream_stdin(connection, stdin_options, &text_options),
+        #[cfg(unix)]
+        TextCommand::Exec {
+            exec_options,
+            text_options,
+        } => crate::stream_exec::stream_exec(connection, exec_options, &text_options),
+        #[cfg(not(unix))]
+        TextCommand::Exec { .. } => Err(crate::error::Error::UnsupportedFormat(
+            "running programs in a terminal is not supported on this platform".to_string(),
+        )),
+        TextCommand::Show {
+            lines,
+            layout_options,
+            text_options,
+        } => show_text(
+            connection,
+            &lines.join("\n"),
+            &layout_options,
+            &text_options,
+        ),
+        TextCommand::File {
+            file_name,
+            layout_options,
+            text_options,
+        } => {
+            let text = read_input(&file_name)?;
+            show_text(
+                connection,
+                &String::from_utf8_lossy(&text),
+                &layout_options,
+                &text_options,
+            )
+        }
     }
 }
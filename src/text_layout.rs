@@ -0,0 +1,235 @@
+use crate::{
+    charset::text_command,
+    cli::{Align, LayoutOptions, TextOptions, VerticalAlign, WrapMode},
+    error::Result,
+    transport::Transport,
+};
+use log::warn;
+use servicepoint::{CharGrid, ClearCommand, Grid, Origin, TILE_HEIGHT, TILE_WIDTH};
+
+/// A rectangle on the display, in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub const FULL: Self = Self {
+        x: 0,
+        y: 0,
+        width: TILE_WIDTH,
+        height: TILE_HEIGHT,
+    };
+}
+
+/// Shows text in one command. The whole region is written, so text shown there before is
+/// replaced.
+pub fn show_text(
+    connection: &Transport,
+    text: &str,
+    options: &LayoutOptions,
+    text_options: &TextOptions,
+) -> Result<()> {
+    let region = options.region.unwrap_or(Region::FULL);
+    let grid = layout(text, region, options);
+    if options.clear {
+        connection.send_command(ClearCommand)?;
+    }
+    connection.send_command(text_command(
+        &grid,
+        Origin::new(region.x, region.y),
+        text_options.charset,
+    ))
+}
+
+/// Places the text in a grid the size of the region. Rows that do not fit are cut off.
+fn layout(text: &str, region: Region, options: &LayoutOptions) -> CharGrid {
+    let mut rows = text
+        .lines()
+        .flat_map(|line| wrap(line, region.width, options.wrap))
+        .collect::<Vec<_>>();
+    if rows.len() > region.height {
+        warn!(
+            "the text needs {} rows, only the first {} are shown",
+            rows.len(),
+            region.height
+        );
+        rows.truncate(region.height);
+    }
+
+    let free_rows = region.height - rows.len();
+    let top = match options.valign {
+        VerticalAlign::Top => 0,
+        VerticalAlign::Middle => free_rows / 2,
+        VerticalAlign::Bottom => free_rows,
+    };
+    let mut grid = CharGrid::new(region.width, region.height);
+    grid.fill(' ');
+    for (index, row) in rows.iter().enumerate() {
+        let free_columns = region.width - row.len();
+        let left = match options.align {
+            Align::Left => 0,
+            Align::Center => free_columns / 2,
+            Align::Right => free_columns,
+        };
+        for (column, &character) in row.iter().enumerate() {
+            grid.set(left + column, top + index, character);
+        }
+    }
+    grid
+}
+
+/// Splits a line into rows of at most `width` characters, without spaces at the end.
+fn wrap(line: &str, width: usize, mode: WrapMode) -> Vec<Vec<char>> {
+    let characters = line.chars().collect::<Vec<_>>();
+    let characters = trim_end(&characters);
+    if characters.is_empty() {
+        return vec![Vec::new()];
+    }
+    match mode {
+        WrapMode::Truncate => vec![characters[..characters.len().min(width)].to_vec()],
+        WrapMode::Char => characters
+            .chunks(width)
+            .map(|row| trim_end(row).to_vec())
+            .collect(),
+        WrapMode::Word => {
+            let mut rows = Vec::new();
+            let mut rest = characters;
+            while rest.len() > width {
+                let indentation = rest.iter().take_while(|&&c| c == ' ').count();
+                // words longer than a row are broken up, also after indentation
+                let end = match rest[..=width].iter().rposition(|&c| c == ' ') {
+                    Some(space) if space >= indentation => space,
+                    _ => width,
+                };
+                rows.push(trim_end(&rest[..end]).to_vec());
+                rest = &rest[end..];
+                let spaces = rest.iter().take_while(|&&c| c == ' ').count();
+                rest = &rest[spaces..];
+            }
+            rows.push(rest.to_vec());
+            rows
+        }
+    }
+}
+
+fn trim_end(characters: &[char]) -> &[char] {
+    let length = characters
+        .iter()
+        .rposition(|&c| c != ' ')
+        .map_or(0, |last| last + 1);
+    &characters[..length]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrapped(line: &str, width: usize, mode: WrapMode) -> Vec<String> {
+        wrap(line, width, mode)
+            .into_iter()
+            .map(|row| row.into_iter().collect())
+            .collect()
+    }
+
+    fn laid_out(
+        text: &str,
+        width: usize,
+        height: usize,
+        align: Align,
+        valign: VerticalAlign,
+    ) -> Vec<String> {
+        let options = LayoutOptions {
+            align,
+            valign,
+            region: None,
+            wrap: WrapMode::Word,
+            clear: false,
+        };
+        let region = Region {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        let grid = layout(text, region, &options);
+        (0..grid.height())
+            .map(|y| (0..grid.width()).map(|x| grid.get(x, y)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn word_wrap_at_the_width_boundary() {
+        assert_eq!(wrapped("abcd efgh", 4, WrapMode::Word), ["abcd", "efgh"]);
+        assert_eq!(wrapped("ab cd ef", 5, WrapMode::Word), ["ab cd", "ef"]);
+        assert_eq!(wrapped("ab cd", 5, WrapMode::Word), ["ab cd"]);
+        assert_eq!(wrapped("ab cdef", 5, WrapMode::Word), ["ab", "cdef"]);
+    }
+
+    #[test]
+    fn word_wrap_breaks_long_words() {
+        assert_eq!(
+            wrapped("abcdefghij k", 4, WrapMode::Word),
+            ["abcd", "efgh", "ij k"]
+        );
+        assert_eq!(
+            wrapped("a bcdefgh", 4, WrapMode::Word),
+            ["a", "bcde", "fgh"]
+        );
+        assert_eq!(
+            wrapped("  abcdefghij", 8, WrapMode::Word),
+            ["  abcdef", "ghij"]
+        );
+    }
+
+    #[test]
+    fn other_wrap_modes() {
+        assert_eq!(
+            wrapped("ab cdefg  ", 3, WrapMode::Char),
+            ["ab", "cde", "fg"]
+        );
+        assert_eq!(wrapped("abcdefg", 3, WrapMode::Truncate), ["abc"]);
+        assert_eq!(wrapped("   ", 3, WrapMode::Word), [""]);
+    }
+
+    #[test]
+    fn horizontal_alignment() {
+        let text = "ab\nc";
+        assert_eq!(
+            laid_out(text, 5, 2, Align::Left, VerticalAlign::Top),
+            ["ab   ", "c    "]
+        );
+        assert_eq!(
+            laid_out(text, 5, 2, Align::Center, VerticalAlign::Top),
+            [" ab  ", "  c  "]
+        );
+        assert_eq!(
+            laid_out(text, 5, 2, Align::Right, VerticalAlign::Top),
+            ["   ab", "    c"]
+        );
+    }
+
+    #[test]
+    fn vertical_alignment() {
+        let text = "a";
+        assert_eq!(
+            laid_out(text, 1, 4, Align::Left, VerticalAlign::Middle),
+            [" ", "a", " ", " "]
+        );
+        assert_eq!(
+            laid_out(text, 1, 4, Align::Left, VerticalAlign::Bottom),
+            [" ", " ", " ", "a"]
+        );
+    }
+
+    #[test]
+    fn rows_below_the_region_are_cut_off() {
+        assert_eq!(
+            laid_out("a\nb\nc", 1, 2, Align::Left, VerticalAlign::Bottom),
+            ["a", "b"]
+        );
+    }
+}
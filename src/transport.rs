@@ -1,51 +1,259 @@
-use crate::cli::TransportType;
-use servicepoint::{FakeConnection, Packet, UdpSocketExt};
-use std::fmt::Debug;
-use std::net::{TcpStream, UdpSocket};
-use std::sync::Mutex;
-use tungstenite::client::IntoClientRequest;
-use tungstenite::stream::MaybeTlsStream;
-use tungstenite::{ClientRequestBuilder, WebSocket};
+use crate::cli::{DumpFormat, TransportOptions, TransportType};
+use crate::compression::CompressionSelector;
+use crate::dump::dump_packet;
+use crate::error::{Error, Result};
+use crate::file_transport::FileTransport;
+use crate::rate_limit::{self, RateLimiter};
+use crate::recording::Recorder;
+use crate::statistics::Statistics;
+use crate::wall::WallLayout;
+use crate::websocket::WebSocketConnection;
+use log::{debug, warn};
+use servicepoint::{Packet, TypedCommand, UdpSocketExt};
+use std::fmt::Display;
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
-pub enum Transport {
+pub struct Transport {
+    targets: Vec<Target>,
+    recorder: Option<Mutex<Recorder>>,
+    statistics: Option<Mutex<Statistics>>,
+    compression: Mutex<CompressionSelector>,
+    dump: Option<DumpFormat>,
+    wall_layout: WallLayout,
+}
+
+#[derive(Debug)]
+struct Target {
+    destination: String,
+    connection: Connection,
+    /// Only used for network connections.
+    limiter: Option<Mutex<RateLimiter>>,
+}
+
+#[derive(Debug)]
+enum Connection {
     Fake,
     Udp(UdpSocket),
-    WebSocket(Mutex<WebSocket<MaybeTlsStream<TcpStream>>>),
+    WebSocket(WebSocketConnection),
+    File(Box<Mutex<FileTransport>>),
 }
 
 impl Transport {
-    pub fn connect(kind: TransportType, destination: &str) -> Transport {
-        match kind {
-            TransportType::Udp => {
-                Self::Udp(UdpSocket::bind_connect(destination).expect("failed to bind socket"))
+    pub fn connect(options: &TransportOptions) -> Result<Transport> {
+        let wall_layout = options.wall.unwrap_or(WallLayout::SINGLE);
+        if wall_layout != WallLayout::SINGLE && wall_layout.displays() != options.destination.len()
+        {
+            return Err(Error::InvalidArguments(format!(
+                "a wall of {}x{} displays needs {} destinations, but got {}",
+                wall_layout.columns,
+                wall_layout.rows,
+                wall_layout.displays(),
+                options.destination.len()
+            )));
+        }
+
+        // shared, so packets from all destinations end up in one recording
+        let received = match options.websocket_options.record_received.as_deref() {
+            Some(file_name) => Some(Arc::new(Mutex::new(Recorder::create(file_name)?))),
+            None => None,
+        };
+        let targets = options
+            .destination
+            .iter()
+            .map(|destination| {
+                let connection = if options.dry_run {
+                    Connection::Fake
+                } else {
+                    Connection::open(destination, options, &received)?
+                };
+                let limiter = match connection {
+                    Connection::Udp(_) | Connection::WebSocket(_) => {
+                        RateLimiter::new(&options.rate_limit_options).map(Mutex::new)
+                    }
+                    Connection::Fake | Connection::File(_) => None,
+                };
+                Ok(Target {
+                    destination: destination.clone(),
+                    connection,
+                    limiter,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let recorder = match options.record.as_deref() {
+            Some(file_name) => Some(Mutex::new(Recorder::create(file_name)?)),
+            None => None,
+        };
+        let statistics = options.stats.then(|| Mutex::new(Statistics::default()));
+        let compression = CompressionSelector::new(
+            options.compression,
+            rate_limit::byte_rate(&options.rate_limit_options),
+        );
+        Ok(Self {
+            targets,
+            recorder,
+            statistics,
+            compression: Mutex::new(compression),
+            dump: options.dump,
+            wall_layout,
+        })
+    }
+
+    /// Without a wall, all destinations show the same content.
+    pub fn wall_layout(&self) -> WallLayout {
+        self.wall_layout
+    }
+
+    /// Whether a display shows the frames, where late frames are better dropped. Files get every
+    /// frame instead, so they do not depend on how fast frames are processed.
+    pub fn is_live(&self) -> bool {
+        self.targets
+            .iter()
+            .any(|target| !matches!(target.connection, Connection::File(_)))
+    }
+
+    /// Counts a frame that was not sent, e.g. because it was late, for the statistics.
+    pub fn frame_skipped(&self) {
+        if let Some(statistics) = &self.statistics {
+            statistics.lock().unwrap().frame_skipped();
+        }
+    }
+
+    /// Sends the command to all targets. Failures are reported per target, so one unreachable
+    /// display does not stop the others from receiving the command.
+    ///
+    /// Bitmaps are compressed as selected in the options.
+    ///
+    /// Returns the last error if sending failed for all targets.
+    pub(crate) fn send_command(&self, command: impl Into<TypedCommand>) -> Result<()> {
+        let packet = self.compression.lock().unwrap().encode(command.into())?;
+        self.send_to(&self.targets, packet)
+    }
+
+    /// Marks the end of a frame, so the commands sent since the last frame are shown together.
+    /// Only the file transport needs this, as it writes one file per frame.
+    pub(crate) fn end_frame(&self) -> Result<()> {
+        for target in &self.targets {
+            if let Connection::File(file) = &target.connection {
+                file.lock().unwrap().end_frame()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the command only to the display at `index` of the wall, counted row by row.
+    pub(crate) fn send_command_to(
+        &self,
+        index: usize,
+        command: impl Into<TypedCommand>,
+    ) -> Result<()> {
+        let packet = self.compression.lock().unwrap().encode(command.into())?;
+        self.send_to(std::slice::from_ref(&self.targets[index]), packet)
+    }
+
+    /// Sends an already encoded packet to all targets, keeping its compression.
+    pub(crate) fn send_packet(&self, packet: Packet) -> Result<()> {
+        self.send_to(&self.targets, packet)
+    }
+
+    fn send_to(&self, targets: &[Target], packet: Packet) -> Result<()> {
+        if let Some(format) = self.dump {
+            dump_packet(&packet, format)?;
+        }
+        let bytes: Vec<u8> = (&packet).into();
+        if let Some(recorder) = &self.recorder {
+            recorder.lock().unwrap().record(&bytes)?;
+        }
+
+        let mut sent = 0;
+        let mut last_error = None;
+        for target in targets {
+            if let Some(limiter) = &target.limiter {
+                let waited = limiter.lock().unwrap().wait(bytes.len());
+                if let Some(statistics) = &self.statistics {
+                    statistics.lock().unwrap().throttled(waited);
+                }
             }
-            TransportType::WebSocket => {
-                let request = ClientRequestBuilder::new(
-                    destination.parse().expect("Invalid destination url"),
-                )
-                .into_client_request()
-                .unwrap();
-                let (sock, _) =
-                    tungstenite::connect(request).expect("failed to connect to websocket");
-                Self::WebSocket(Mutex::new(sock))
+            match target.send(&bytes) {
+                Ok(()) => sent += 1,
+                Err(e) => {
+                    warn!("{e}");
+                    last_error = Some(e);
+                }
             }
-            TransportType::Fake => Self::Fake,
         }
+        if let Some(statistics) = &self.statistics {
+            statistics.lock().unwrap().packet_sent(&packet, sent);
+        }
+        match last_error {
+            Some(e) if sent == 0 => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Connection {
+    /// Destinations starting with `udp://`, `ws://`, `wss://` or `file://` use the matching
+    /// transport, others use the transport from the options.
+    fn open(
+        destination: &str,
+        options: &TransportOptions,
+        received: &Option<Arc<Mutex<Recorder>>>,
+    ) -> Result<Self> {
+        let (kind, destination) = match destination.split_once("://") {
+            Some(("udp", address)) => (TransportType::Udp, address),
+            Some(("ws" | "wss", _)) => (TransportType::WebSocket, destination),
+            Some(("file", directory)) => (TransportType::File, directory),
+            Some((scheme, _)) => {
+                return Err(Error::InvalidArguments(format!(
+                    "unsupported scheme {scheme} in destination {destination}"
+                )));
+            }
+            None => (options.transport, destination),
+        };
+        debug!("connecting to {destination} via {kind:?}");
+
+        let connection = match kind {
+            TransportType::Udp => Self::Udp(UdpSocket::bind_connect(destination).map_err(|e| {
+                Error::Connection(format!("failed to connect to {destination}: {e}"))
+            })?),
+            TransportType::WebSocket => Self::WebSocket(WebSocketConnection::connect(
+                destination,
+                options,
+                received.clone(),
+            )?),
+            TransportType::Fake => Self::Fake,
+            TransportType::File => Self::File(Box::new(Mutex::new(FileTransport::new(
+                destination,
+                options.file_format,
+            )?))),
+        };
+        Ok(connection)
     }
+}
 
-    pub(crate) fn send_command<T: TryInto<Packet>>(&self, command: T) -> Option<()>
-    where
-        <T as TryInto<Packet>>::Error: Debug,
-    {
-        match self {
-            Self::Udp(socket) => socket.send_command(command),
-            Self::WebSocket(socket) => {
-                let bytes: Vec<u8> = command.try_into().unwrap().into();
-                let mut socket = socket.lock().unwrap();
-                socket.send(tungstenite::Message::Binary(bytes.into())).ok()
+impl Target {
+    fn send(&self, bytes: &[u8]) -> Result<()> {
+        let connection_error = |e: &dyn Display| {
+            Error::Connection(format!("failed to send to {}: {e}", self.destination))
+        };
+        match &self.connection {
+            Connection::Udp(socket) => socket
+                .send(bytes)
+                .map(|_| ())
+                .map_err(|e| connection_error(&e)),
+            Connection::WebSocket(socket) => socket.send(bytes),
+            Connection::Fake => Ok(()),
+            Connection::File(file) => {
+                // decode the packet again, so the result matches what the display would show
+                let packet =
+                    Packet::try_from(bytes).map_err(|e| Error::Encode(format!("{e:?}")))?;
+                let command =
+                    TypedCommand::try_from(packet).map_err(|e| Error::Encode(format!("{e:?}")))?;
+                file.lock().unwrap().send(command);
+                Ok(())
             }
-            Self::Fake => FakeConnection.send_command(command),
         }
     }
 }
@@ -0,0 +1,104 @@
+use crate::{
+    cli::FrameDiffOptions, error::Result, frame_diff::FrameDiffer,
+    image_processing::ProcessedFrame, transport::Transport,
+};
+use log::debug;
+use servicepoint::{
+    Bitmap, BrightnessGrid, Grid, PIXEL_HEIGHT, PIXEL_WIDTH, TILE_HEIGHT, TILE_WIDTH,
+};
+
+/// Displays placed next to each other to form one large screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WallLayout {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl WallLayout {
+    pub const SINGLE: Self = Self {
+        columns: 1,
+        rows: 1,
+    };
+
+    pub fn displays(&self) -> usize {
+        self.columns * self.rows
+    }
+}
+
+/// Sends processed frames to the displays, each display only receiving its part of the frame.
+///
+/// Every display has its own [FrameDiffer], so only the changed tiles are sent to each of them.
+#[derive(Debug)]
+pub struct WallSender<'t> {
+    connection: &'t Transport,
+    layout: WallLayout,
+    differs: Vec<FrameDiffer>,
+}
+
+impl<'t> WallSender<'t> {
+    pub fn new(connection: &'t Transport, options: FrameDiffOptions) -> Self {
+        let layout = connection.wall_layout();
+        debug!("sending frames to a wall of {layout:?}");
+        Self {
+            connection,
+            layout,
+            differs: (0..layout.displays())
+                .map(|_| FrameDiffer::new(options.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn send(&mut self, frame: ProcessedFrame) -> Result<()> {
+        if self.layout == WallLayout::SINGLE {
+            for command in self.differs[0].diff(frame) {
+                self.connection.send_command(command)?;
+            }
+            return self.connection.end_frame();
+        }
+
+        for row in 0..self.layout.rows {
+            for column in 0..self.layout.columns {
+                let index = row * self.layout.columns + column;
+                let part = Self::slice(&frame, column, row);
+                for command in self.differs[index].diff(part) {
+                    self.connection.send_command_to(index, command)?;
+                }
+            }
+        }
+        self.connection.end_frame()
+    }
+
+    /// Counts a frame that was dropped instead of being sent.
+    pub fn skip(&self) {
+        self.connection.frame_skipped();
+    }
+
+    /// Cuts out the part of the frame shown on one display. Areas the frame does not cover,
+    /// e.g. because of the aspect ratio, stay dark.
+    fn slice(frame: &ProcessedFrame, column: usize, row: usize) -> ProcessedFrame {
+        let (offset_x, offset_y) = (column * PIXEL_WIDTH, row * PIXEL_HEIGHT);
+        let mut bitmap = Bitmap::max_sized();
+        for y in 0..PIXEL_HEIGHT {
+            for x in 0..PIXEL_WIDTH {
+                if let Some(value) = frame.bitmap.get_optional(offset_x + x, offset_y + y) {
+                    bitmap.set(x, y, value);
+                }
+            }
+        }
+
+        let brightness = frame.brightness.as_ref().map(|grid| {
+            let (offset_x, offset_y) = (column * TILE_WIDTH, row * TILE_HEIGHT);
+            let mut part = BrightnessGrid::new(TILE_WIDTH, TILE_HEIGHT);
+            for y in 0..TILE_HEIGHT {
+                for x in 0..TILE_WIDTH {
+                    if let Some(value) = grid.get_optional(offset_x + x, offset_y + y) {
+                        part.set(x, y, value);
+                    }
+                }
+            }
+            part
+        });
+
+        ProcessedFrame { bitmap, brightness }
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{
+    cli::{ReconnectOptions, TransportOptions, WebSocketOptions},
+    error::{Error, Result},
+    recording::Recorder,
+};
+use data_encoding::BASE64;
+use log::{debug, info, trace, warn};
+use native_tls::{Certificate, TlsConnector};
+use std::{
+    fmt::Display,
+    io::ErrorKind,
+    net::TcpStream,
+    sync::{Arc, Mutex, Weak},
+    thread::{self, sleep},
+    time::Duration,
+};
+use tungstenite::{
+    ClientRequestBuilder, Connector, HandshakeError, Message, WebSocket, client::IntoClientRequest,
+    protocol::frame::coding::CloseCode, stream::MaybeTlsStream,
+};
+
+/// How often the reader checks for incoming messages, e.g. to answer pings.
+const READ_INTERVAL: Duration = Duration::from_millis(50);
+/// How long the reader waits for the next message while it holds the connection.
+const READ_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// A WebSocket connection that is reopened when it gets lost, e.g. because the relay restarted.
+///
+/// Reconnecting happens in the background. Packets sent in the meantime are dropped, so a lost
+/// destination does not hold up the others. Once all reconnect attempts failed, sending returns
+/// an error.
+///
+/// Incoming messages are handled by a background thread, which answers pings, logs messages and
+/// close reasons of the server and optionally records received packets.
+#[derive(Debug)]
+pub struct WebSocketConnection {
+    shared: Arc<Shared>,
+}
+
+/// Everything needed to open the connection again, so reconnecting does not need the lock.
+struct Shared {
+    destination: String,
+    request: ClientRequestBuilder,
+    tls: TlsConnector,
+    options: ReconnectOptions,
+    state: Mutex<State>,
+}
+
+/// Written by hand, as the request contains the credentials.
+impl std::fmt::Debug for Shared {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shared")
+            .field("destination", &self.destination)
+            .field("options", &self.options)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    link: Link,
+    received: Option<Arc<Mutex<Recorder>>>,
+}
+
+#[derive(Debug)]
+enum Link {
+    Open(Box<WebSocket<MaybeTlsStream<TcpStream>>>),
+    Reconnecting,
+    /// All reconnect attempts failed.
+    Lost,
+}
+
+impl WebSocketConnection {
+    /// The first connection attempt is not retried, so wrong destinations fail fast.
+    pub fn connect(
+        destination: &str,
+        options: &TransportOptions,
+        received: Option<Arc<Mutex<Recorder>>>,
+    ) -> Result<Self> {
+        let mut shared = Shared {
+            destination: destination.to_string(),
+            request: Self::build_request(destination, &options.websocket_options)?,
+            tls: Self::tls_connector(&options.websocket_options)?,
+            options: options.reconnect_options.clone(),
+            state: Mutex::new(State {
+                link: Link::Lost,
+                received,
+            }),
+        };
+        let socket = shared.open()?;
+        shared.state.get_mut().unwrap().link = Link::Open(Box::new(socket));
+
+        let shared = Arc::new(shared);
+        let reader = Arc::downgrade(&shared);
+        thread::spawn(move || read_messages(reader));
+        Ok(Self { shared })
+    }
+
+    fn build_request(
+        destination: &str,
+        options: &WebSocketOptions,
+    ) -> Result<ClientRequestBuilder> {
+        let uri = destination.parse().map_err(|e| {
+            Error::InvalidArguments(format!("invalid destination url {destination}: {e}"))
+        })?;
+        let mut request = ClientRequestBuilder::new(uri);
+        for (name, value) in &options.headers {
+            request = request.with_header(name, value);
+        }
+        if let Some(authorization) = Self::authorization(options)? {
+            request = request.with_header("Authorization", authorization);
+        }
+        for protocol in &options.subprotocols {
+            request = request.with_sub_protocol(protocol);
+        }
+
+        // check the headers now, so invalid ones do not show up as connection errors later
+        request
+            .clone()
+            .into_client_request()
+            .map_err(|e| Error::InvalidArguments(format!("invalid websocket request: {e}")))?;
+        Ok(request)
+    }
+
+    fn authorization(options: &WebSocketOptions) -> Result<Option<String>> {
+        if let Some(credentials) = &options.basic_auth {
+            return Ok(Some(format!(
+                "Basic {}",
+                BASE64.encode(credentials.as_bytes())
+            )));
+        }
+
+        let token = match &options.token_file {
+            Some(file) => Some(
+                std::fs::read_to_string(file)
+                    .map_err(|e| Error::io(format!("failed to read token file {file}"), e))?
+                    .trim()
+                    .to_string(),
+            ),
+            None => options.token.clone(),
+        };
+        Ok(token.map(|token| format!("Bearer {token}")))
+    }
+
+    fn tls_connector(options: &WebSocketOptions) -> Result<TlsConnector> {
+        let mut builder = TlsConnector::builder();
+        for file in &options.ca_certs {
+            let pem = std::fs::read(file)
+                .map_err(|e| Error::io(format!("failed to read certificate file {file}"), e))?;
+            let certificates = Certificate::stack_from_pem(&pem)
+                .map_err(|e| Error::Decode(format!("invalid certificate in {file}: {e}")))?;
+            if certificates.is_empty() {
+                return Err(Error::Decode(format!("no certificates found in {file}")));
+            }
+            for certificate in certificates {
+                builder.add_root_certificate(certificate);
+            }
+        }
+        if options.insecure {
+            warn!("not verifying TLS certificates of WebSocket destinations");
+            builder
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true);
+        }
+        builder
+            .build()
+            .map_err(|e| Error::Connection(format!("failed to set up TLS: {e}")))
+    }
+
+    pub fn send(&self, bytes: &[u8]) -> Result<()> {
+        self.shared.send(bytes)
+    }
+}
+
+impl Shared {
+    fn open(&self) -> Result<WebSocket<MaybeTlsStream<TcpStream>>> {
+        let connection_error = |e: &dyn Display| {
+            Error::Connection(format!("failed to connect to {}: {e}", self.destination))
+        };
+
+        let request = self
+            .request
+            .clone()
+            .into_client_request()
+            .map_err(|e| connection_error(&e))?;
+        let uri = request.uri();
+        let host = uri
+            .host()
+            .ok_or_else(|| connection_error(&"missing host name"))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let default_port = if uri.scheme_str() == Some("wss") {
+            443
+        } else {
+            80
+        };
+        let port = uri.port_u16().unwrap_or(default_port);
+
+        let stream = TcpStream::connect((host, port)).map_err(|e| connection_error(&e))?;
+        stream.set_nodelay(true).map_err(|e| connection_error(&e))?;
+        let connector = Connector::NativeTls(self.tls.clone());
+        let (socket, _) =
+            tungstenite::client_tls_with_config(request, stream, None, Some(connector)).map_err(
+                |e| match e {
+                    HandshakeError::Failure(e) => connection_error(&e),
+                    HandshakeError::Interrupted(_) => {
+                        unreachable!("blocking handshake was interrupted")
+                    }
+                },
+            )?;
+
+        // only set after the handshake, which needs blocking reads
+        let stream = match socket.get_ref() {
+            MaybeTlsStream::Plain(stream) => stream,
+            MaybeTlsStream::NativeTls(stream) => stream.get_ref(),
+            _ => unreachable!("only native-tls is enabled"),
+        };
+        stream
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .map_err(|e| connection_error(&e))?;
+        Ok(socket)
+    }
+
+    fn send(self: &Arc<Self>, bytes: &[u8]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match &mut state.link {
+            Link::Open(socket) => {
+                if let Err(e) = socket.send(Message::Binary(bytes.to_vec().into())) {
+                    warn!("lost websocket connection to {}: {e}", self.destination);
+                    self.start_reconnecting(&mut state);
+                }
+                Ok(())
+            }
+            Link::Reconnecting => {
+                trace!("dropped packet for {} while reconnecting", self.destination);
+                Ok(())
+            }
+            Link::Lost => Err(Error::Connection(format!(
+                "lost connection to {}",
+                self.destination
+            ))),
+        }
+    }
+
+    fn start_reconnecting(self: &Arc<Self>, state: &mut State) {
+        state.link = Link::Reconnecting;
+        let shared = Arc::downgrade(self);
+        thread::spawn(move || reconnect(shared));
+    }
+
+    /// Handles all messages that arrived since the last call.
+    fn read_pending(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let Link::Open(socket) = &mut state.link else {
+            return;
+        };
+
+        loop {
+            let message = match socket.read() {
+                Ok(message) => message,
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    break;
+                }
+                Err(tungstenite::Error::ConnectionClosed) => {
+                    debug!("websocket connection to {} closed", self.destination);
+                    self.start_reconnecting(state);
+                    return;
+                }
+                Err(e) => {
+                    warn!("lost websocket connection to {}: {e}", self.destination);
+                    self.start_reconnecting(state);
+                    return;
+                }
+            };
+
+            match message {
+                Message::Binary(bytes) => {
+                    trace!("received {} bytes from {}", bytes.len(), self.destination);
+                    if let Some(recorder) = &state.received {
+                        if let Err(e) = recorder.lock().unwrap().record(&bytes) {
+                            warn!("{e}");
+                        }
+                    }
+                }
+                Message::Text(text) => info!("message from {}: {text}", self.destination),
+                Message::Close(Some(frame)) if frame.code != CloseCode::Normal => {
+                    warn!("{} closed the connection: {frame}", self.destination);
+                }
+                Message::Close(_) => info!("{} closed the connection", self.destination),
+                // pings are answered by tungstenite
+                Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {}
+            }
+        }
+
+        // sends the answers to pings and close frames
+        if let Err(e) = socket.flush() {
+            warn!("lost websocket connection to {}: {e}", self.destination);
+            self.start_reconnecting(state);
+        }
+    }
+}
+
+/// Runs until the connection is dropped.
+fn read_messages(shared: Weak<Shared>) {
+    loop {
+        sleep(READ_INTERVAL);
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        shared.read_pending();
+    }
+}
+
+/// Tries to connect again, doubling the delay after every failed attempt. Stops early when the
+/// connection is dropped.
+fn reconnect(shared: Weak<Shared>) {
+    let Some(options) = shared.upgrade().map(|shared| shared.options.clone()) else {
+        return;
+    };
+    let mut delay = options.reconnect_delay.min(options.reconnect_max_delay);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        sleep(delay);
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        info!("reconnecting to {} (attempt {attempt})", shared.destination);
+
+        // the lock is only taken afterward, so sending and reading are not blocked meanwhile
+        let link = match shared.open() {
+            Ok(socket) => {
+                info!("reconnected to {}", shared.destination);
+                Link::Open(Box::new(socket))
+            }
+            Err(e) if attempt == options.reconnect_attempts => {
+                warn!("{e}");
+                warn!("giving up after {attempt} reconnect attempts");
+                Link::Lost
+            }
+            Err(e) => {
+                warn!("{e}");
+                delay = delay.saturating_mul(2).min(options.reconnect_max_delay);
+                continue;
+            }
+        };
+        shared.state.lock().unwrap().link = link;
+        return;
+    }
+}